@@ -0,0 +1,31 @@
+// BENCHMARKS OF THE SOLAR TIMETABLE
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use flowy::Timetable;
+
+/// 2024-06-21 00:00 UTC
+const MIDSUMMER: f64 = 1_718_928_000.0;
+
+/// Moving through one day a minute at a time, as the daemon does,
+/// by building a new timetable each time and by moving one timetable along
+fn set_date(c: &mut Criterion) {
+    let minutes = || (0..24 * 60).map(|minute| MIDSUMMER + minute as f64 * 60.0);
+    c.bench_function("new every minute", |b| {
+        b.iter(|| {
+            for date in minutes() {
+                black_box(Timetable::new(black_box(date), 51.5, -0.1));
+            }
+        })
+    });
+    c.bench_function("set_date every minute", |b| {
+        let mut tt = Timetable::new(MIDSUMMER, 51.5, -0.1);
+        b.iter(|| {
+            for date in minutes() {
+                tt.set_date(black_box(date));
+            }
+            black_box(&tt);
+        })
+    });
+}
+
+criterion_group!(benches, set_date);
+criterion_main!(benches);
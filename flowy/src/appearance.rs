@@ -0,0 +1,42 @@
+// THIS MODULE SWITCHES MACOS BETWEEN ITS LIGHT AND DARK APPEARANCE
+use std::error::Error;
+#[cfg(target_os = "macos")]
+use std::process::Command;
+
+/// Whether the appearance can be switched here
+pub const SUPPORTED: bool = cfg!(target_os = "macos");
+
+/// Whether macOS shows its dark appearance
+pub(crate) fn is_dark() -> Result<bool, Box<dyn Error>> {
+    #[cfg(target_os = "macos")]
+    {
+        // AppleInterfaceStyle is only set in dark mode
+        let output = Command::new("defaults")
+            .args(["read", "-g", "AppleInterfaceStyle"])
+            .output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim() == "Dark")
+    }
+    #[cfg(not(target_os = "macos"))]
+    Err("The appearance can only be switched on macOS".into())
+}
+
+/// Switches macOS to its dark or light appearance
+pub(crate) fn set_dark(dark: bool) -> Result<(), Box<dyn Error>> {
+    #[cfg(target_os = "macos")]
+    {
+        let script = format!(
+            "tell application \"System Events\" to tell appearance preferences to set dark mode to {}",
+            dark
+        );
+        let output = Command::new("osascript").args(["-e", &script]).output()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = dark;
+        Err("The appearance can only be switched on macOS".into())
+    }
+}
@@ -0,0 +1,99 @@
+// THIS MODULE MANAGES THE FILES FLOWY CACHES
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Returns the cache directory, creating it if needed.
+/// Everything in it can be computed again, so it may be deleted at any time.
+pub(crate) fn get_cache_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let mut cache_dir = crate::get_config_dir()?;
+    cache_dir.push("cache");
+    std::fs::create_dir_all(&cache_dir)?;
+    Ok(cache_dir)
+}
+
+/// A file in the cache
+struct CachedFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Returns the files below `dir`
+fn cached_files(dir: &Path) -> Result<Vec<CachedFile>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            files.extend(cached_files(&entry.path())?);
+        } else {
+            files.push(CachedFile {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// Formats a number of bytes for people, e.g. `1.5 MB`
+fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Prints each entry of the cache directory with its size, and the total
+pub fn show_cache() -> Result<(), Box<dyn Error>> {
+    let cache_dir = get_cache_dir()?;
+    println!("Cache: {}", cache_dir.display());
+    let mut entries: Vec<_> = std::fs::read_dir(&cache_dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    let mut total = 0;
+    for entry in entries {
+        let size: u64 = if entry.metadata()?.is_dir() {
+            cached_files(&entry.path())?.iter().map(|f| f.size).sum()
+        } else {
+            entry.metadata()?.len()
+        };
+        total += size;
+        println!("- {} {}", entry.file_name().to_string_lossy(), human_size(size));
+    }
+    println!("Total: {}", human_size(total));
+    Ok(())
+}
+
+/// Deletes cached files, or only those not modified within `older_than`.
+/// Files the config uses as wallpapers are kept.
+pub fn clean_cache(older_than: Option<Duration>) -> Result<(), Box<dyn Error>> {
+    let cache_dir = get_cache_dir()?;
+    let in_use: Vec<PathBuf> = crate::get_config()
+        .map(|config| config.walls.iter().map(|w| crate::images::file_path(w)).collect())
+        .unwrap_or_default();
+    let now = SystemTime::now();
+
+    let (mut removed, mut freed) = (0, 0);
+    for file in cached_files(&cache_dir)? {
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        if older_than.is_some_and(|older_than| age < older_than) || in_use.contains(&file.path) {
+            continue;
+        }
+        std::fs::remove_file(&file.path)?;
+        removed += 1;
+        freed += file.size;
+    }
+    println!("Removed {} files, freeing {}", removed, human_size(freed));
+    Ok(())
+}
@@ -0,0 +1,274 @@
+// THIS MODULE DEFINES THE COMMAND LINE ARGUMENTS
+use clap::{AppSettings, Clap};
+
+/// Changes wallpaper dynamically
+#[derive(Clap, Debug)]
+#[clap(name = "flowy", version = "0.4.0", author = "Vineet Reddy <vineetreddy@live.com>")]
+#[clap(setting = AppSettings::ArgRequiredElseHelp)]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Also logs details such as download requests
+    #[clap(short, long, conflicts_with = "quiet")]
+    pub verbose: bool,
+
+    /// Only logs errors
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// Also logs wallpaper changes and errors to flowy.log in the config folder
+    #[clap(long)]
+    pub log_file: bool,
+
+    /// Tells flowy where your wallpapers are
+    #[clap(short, long, value_name = "DIR PATH")]
+    pub dir: Option<String>,
+
+    /// Tells flowy what preset to get, by name (lake) or the URL of a .tar.gz file
+    #[clap(short, long, value_name = "PRESET NAME OR URL")]
+    pub preset: Option<String>,
+
+    /// SHA-256 digest the downloaded preset must have
+    #[clap(long, value_name = "HEX", requires = "preset")]
+    pub preset_sha256: Option<String>,
+
+    /// Flowy is setup with Sunset and Sunrise times. LAT and LONG may be left out with --geo-auto
+    #[clap(
+        short,
+        long,
+        value_name = "DIR [LAT LONG]",
+        min_values = 1,
+        max_values = 3,
+        allow_hyphen_values = true
+    )]
+    pub solar: Option<Vec<String>>,
+
+    /// Writes the generated times and wallpapers to FILE
+    #[clap(long, value_name = "FILE")]
+    pub dump_schedule: Option<String>,
+
+    /// Order in which to try external wallpaper tools, e.g. xwallpaper,feh
+    #[clap(long, value_name = "TOOL,TOOL...")]
+    pub prefer_tool: Option<String>,
+
+    /// Times setting the wallpaper N times (default 5) and exits
+    #[clap(long, value_name = "N")]
+    pub benchmark: Option<Option<usize>>,
+
+    /// Reuses the wallpapers of another config with new times. The DIR of --solar is ignored
+    #[clap(long, value_name = "CONFIG")]
+    pub images_from_config: Option<String>,
+
+    /// Stops a running daemon from changing the wallpaper
+    #[clap(long, conflicts_with = "resume")]
+    pub pause: bool,
+
+    /// Lets a paused daemon change the wallpaper again
+    #[clap(long)]
+    pub resume: bool,
+
+    /// Orders the wallpapers of --dir by colour, warm around sunrise and sunset and cool otherwise.
+    /// It assumes an even split, so it can't be used with --weights or --window
+    #[clap(long, requires = "dir", conflicts_with_all = &["weights", "window"])]
+    pub by_color_temp: bool,
+
+    /// Share of the day for each wallpaper of --dir in order, e.g. 1,1,6,2. Evenly split by default
+    #[clap(long, value_name = "WEIGHT,WEIGHT...", requires = "dir")]
+    pub weights: Option<String>,
+
+    /// Spreads the wallpapers of --dir over this part of the day only, e.g. 08:00-22:00. May span midnight
+    #[clap(long, value_name = "HH:MM-HH:MM", requires = "dir")]
+    pub window: Option<String>,
+
+    /// Wallpaper shown outside --window. The last wallpaper stays by default
+    #[clap(long, value_name = "FILE", requires = "window")]
+    pub outside_wallpaper: Option<String>,
+
+    /// Scaling of the wallpaper, one of fill, fit, center, stretch and tile. Overrides mode in the config
+    #[clap(long, value_name = "MODE")]
+    pub fit: Option<String>,
+
+    /// Seconds each wallpaper fades in for, with swww on Sway and River. Overrides transition in the config
+    #[clap(long, value_name = "SECS")]
+    pub transition: Option<f64>,
+
+    /// Scaling for each monitor, e.g. 0=fill,1=fit. Modes are fill, fit, center, stretch and tile
+    #[clap(long, value_name = "MONITOR=MODE,...")]
+    pub fit_per_monitor: Option<String>,
+
+    /// Prints the wallpaper which should be displayed now and exits
+    #[clap(long)]
+    pub current: bool,
+
+    /// Prints the detected desktop, the variables it was detected from, the config path and the current wallpaper, and exits
+    #[clap(long)]
+    pub list_environments: bool,
+
+    /// Sets the wallpaper for now once and exits, for running flowy from cron or a timer
+    #[clap(long)]
+    pub apply: bool,
+
+    /// Prints the schedule and the wallpaper for now without setting any wallpaper
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Compares the wallpaper on the desktop with the scheduled one and exits, failing if they differ
+    #[clap(long)]
+    pub audit: bool,
+
+    /// Explains how the current wallpaper was picked
+    #[clap(long, requires = "current")]
+    pub explain: bool,
+
+    /// Gradually blends each wallpaper into the next in STEPS steps (default 10). CPU heavy
+    #[clap(long, value_name = "STEPS")]
+    pub morph: Option<Option<u32>>,
+
+    /// Reads and writes the config at PATH instead of the one in flowy's config folder
+    #[clap(long, value_name = "PATH")]
+    pub config: Option<String>,
+
+    /// Reads and writes the config in the [NAME] table of the config file
+    #[clap(long, value_name = "NAME")]
+    pub config_section: Option<String>,
+
+    /// Reads and writes config.FORMAT, where FORMAT is toml (default), json or yaml
+    #[clap(long, value_name = "FORMAT")]
+    pub config_format: Option<String>,
+
+    /// Imports a KDE wallpaper package or slideshow folder
+    #[clap(long, value_name = "PATH")]
+    pub from_wallpaper_of: Option<String>,
+
+    /// Sets the wallpaper again when monitors are connected or removed.
+    /// Polls xrandr every minute, so it only works on X11
+    #[clap(long)]
+    pub monitor_hotplug: bool,
+
+    /// Prints a commented example config and exits
+    #[clap(long)]
+    pub config_template: bool,
+
+    /// Longest time between checks of the schedule, e.g. 10s or 30m. 5m by default
+    #[clap(long, value_name = "DURATION")]
+    pub interval: Option<String>,
+
+    /// Looks up LAT and LONG for --solar from your IP address when they are left out. The result is saved in the config
+    #[clap(long, requires = "solar")]
+    pub geo_auto: bool,
+
+    /// Sets the wallpapers of the --solar DIR in order at these solar events instead of splitting them at sunrise and sunset. One of astro-dawn, naut-dawn, civil-dawn, sunrise, noon, sunset, civil-dusk, naut-dusk, astro-dusk and midnight for each wallpaper
+    #[clap(long, value_name = "PHASE,...", requires = "solar", conflicts_with = "images-from-config")]
+    pub phases: Option<String>,
+
+    /// Part of the name of the day wallpapers in solar mode, in any case. DAY by default
+    #[clap(long, value_name = "TAG", requires = "solar")]
+    pub day_tag: Option<String>,
+
+    /// Part of the name of the night wallpapers in solar mode, in any case. NIGHT by default
+    #[clap(long, value_name = "TAG", requires = "solar")]
+    pub night_tag: Option<String>,
+
+    /// How often to recompute sunrise and sunset in solar mode, e.g. 30m or 6h. Once a day by default
+    #[clap(long, value_name = "DURATION", requires = "solar")]
+    pub solar_update_interval: Option<String>,
+
+    /// Switches macOS to the light appearance at sunrise and the dark one at sunset. Needs a solar schedule
+    #[clap(long)]
+    pub appearance: bool,
+
+    /// Only changes the wallpaper within these windows, e.g. 08:00-23:00
+    #[clap(long, value_name = "HH:MM-HH:MM,...")]
+    pub active_hours: Option<String>,
+
+    /// TOML file of dates (YYYY-MM-DD or MM-DD) and wallpaper directories to use all day on them
+    #[clap(long, value_name = "FILE")]
+    pub holiday_file: Option<String>,
+
+    /// Changes the wallpapers in DIR, darkest first, as the sun rises and sets through equal bands of elevation
+    #[clap(long, value_name = "LAT LONG", number_of_values = 2, allow_hyphen_values = true, requires = "dir")]
+    pub elevation_steps: Option<Vec<f64>>,
+
+    /// Checks a config file and makes it flowy's config, backing up the current one
+    #[clap(long, value_name = "PATH OR URL")]
+    pub copy_config_from: Option<String>,
+
+    /// Prints the current elevation of the sun in degrees and the part of the day
+    #[clap(long, value_name = "LAT LONG", number_of_values = 2, allow_hyphen_values = true)]
+    pub print_sun_now: Option<Vec<f64>>,
+
+    /// Prints --print-sun-now as JSON
+    #[clap(long, requires = "print-sun-now")]
+    pub json: bool,
+
+    /// Order of the wallpapers, one of natural (default), name, mtime or random
+    #[clap(long, value_name = "ORDER")]
+    pub sort: Option<String>,
+
+    /// Shows the wallpapers in a random order, the same as --sort random
+    #[clap(long, conflicts_with = "sort")]
+    pub shuffle: bool,
+
+    /// Seed of the random order, so that the same seed gives the same order
+    #[clap(long, value_name = "NUMBER")]
+    pub seed: Option<u64>,
+
+    /// Stores the targets of symlinked wallpapers, skipping broken links
+    #[clap(long)]
+    pub resolve_symlinks: bool,
+
+    /// Also uses the wallpapers in subfolders of the wallpaper folder
+    #[clap(short, long)]
+    pub recursive: bool,
+
+    /// Skips wallpapers which are empty or not images, such as truncated downloads. Slower for large folders
+    #[clap(long)]
+    pub validate_images: bool,
+
+    /// Converts wallpapers in formats the desktop can't show, such as WebP, to PNG copies in the cache
+    #[clap(long)]
+    pub convert: bool,
+
+    /// Sets IMAGE and keeps setting it again if another program changes the wallpaper
+    #[clap(long, value_name = "IMAGE")]
+    pub set_and_watch: Option<String>,
+}
+
+#[derive(Clap, Debug)]
+pub enum Command {
+    /// Prints a completion script for bash, zsh, fish or powershell
+    #[clap(setting = AppSettings::Hidden)]
+    Completions {
+        /// The shell to complete in
+        #[clap(possible_values = &["bash", "zsh", "fish", "powershell"])]
+        shell: String,
+    },
+    /// Makes a config from the wallpapers in your Pictures folder, preferring its Wallpapers subfolder
+    Init {
+        /// Replaces an existing config
+        #[clap(long)]
+        force: bool,
+    },
+    /// Checks that every wallpaper in the config exists and is readable
+    VerifyConfig,
+    /// Prints the current and the next wallpaper with their times
+    Status,
+    /// Shows or cleans the files flowy has cached
+    Cache {
+        #[clap(subcommand)]
+        command: Option<CacheCommand>,
+    },
+}
+
+#[derive(Clap, Debug)]
+pub enum CacheCommand {
+    /// Lists the cache with the size of each entry
+    Show,
+    /// Deletes cached files, which are made again when needed
+    Clean {
+        /// Only deletes files not changed in this many days
+        #[clap(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+    },
+}
@@ -0,0 +1,124 @@
+// THIS MODULE PRINTS SHELL COMPLETION SCRIPTS FOR THE CLI
+use clap::{App, AppSettings, Arg, ArgSettings};
+use std::error::Error;
+
+/// Shells a completion script can be printed for
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+/// Prints the completion script of `app` for `shell` to stdout
+pub fn print_completions(app: &App, shell: &str) -> Result<(), Box<dyn Error>> {
+    let name = app.get_name();
+    let script = match shell {
+        "bash" => bash(app, name),
+        "zsh" => zsh(app, name),
+        "fish" => fish(app, name),
+        "powershell" => powershell(app, name),
+        _ => return Err(format!("Unknown shell {}, expected one of {}", shell, SHELLS.join(", ")).into()),
+    };
+    print!("{}", script);
+    Ok(())
+}
+
+/// Arguments shown in --help
+fn visible_args<'a, 'help>(app: &'a App<'help>) -> impl Iterator<Item = &'a Arg<'help>> {
+    app.get_arguments().filter(|a| !a.is_set(ArgSettings::Hidden))
+}
+
+/// Subcommands shown in --help
+fn visible_subcommands<'a, 'help>(app: &'a App<'help>) -> impl Iterator<Item = &'a App<'help>> {
+    app.get_subcommands().filter(|s| !s.is_set(AppSettings::Hidden))
+}
+
+/// The -s and --long spellings of an argument
+fn flags(arg: &Arg) -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("-{}", short));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("--{}", long));
+    }
+    flags
+}
+
+fn bash(app: &App, name: &str) -> String {
+    let mut words: Vec<String> = visible_subcommands(app).map(|s| s.get_name().to_string()).collect();
+    let mut with_value = Vec::new();
+    for arg in visible_args(app) {
+        if arg.is_set(ArgSettings::TakesValue) {
+            with_value.extend(flags(arg));
+        }
+        words.extend(flags(arg));
+    }
+    let mut script = format!("_{}() {{\n", name);
+    script.push_str("    local cur prev\n");
+    script.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    // Values are completed as paths
+    if !with_value.is_empty() {
+        script.push_str(&format!("    case \"$prev\" in\n        {})\n", with_value.join("|")));
+        script.push_str("            COMPREPLY=($(compgen -f -- \"$cur\"))\n            return 0\n            ;;\n    esac\n");
+    }
+    script.push_str(&format!("    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", words.join(" ")));
+    script.push_str(&format!("}}\ncomplete -o default -F _{} {}\n", name, name));
+    script
+}
+
+fn zsh(app: &App, name: &str) -> String {
+    // Quoted for a single quoted string, with the brackets of the description escaped
+    let escape = |s: &str| s.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]");
+    let mut script = format!("#compdef {}\n\n_arguments \\\n", name);
+    for arg in visible_args(app) {
+        let about = escape(arg.get_about().unwrap_or_default());
+        let value = if arg.is_set(ArgSettings::TakesValue) { ":value:_files" } else { "" };
+        for flag in flags(arg) {
+            script.push_str(&format!("    '{}[{}]{}' \\\n", flag, about, value));
+        }
+    }
+    let subcommands: Vec<_> = visible_subcommands(app).map(|s| s.get_name()).collect();
+    script.push_str(&format!("    '1::command:({})'\n", subcommands.join(" ")));
+    script
+}
+
+fn fish(app: &App, name: &str) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('\'', "\\'");
+    let mut script = String::new();
+    for subcommand in visible_subcommands(app) {
+        script.push_str(&format!(
+            "complete -c {} -n __fish_use_subcommand -f -a {} -d '{}'\n",
+            name,
+            subcommand.get_name(),
+            escape(subcommand.get_about().unwrap_or_default())
+        ));
+    }
+    for arg in visible_args(app) {
+        let mut line = format!("complete -c {}", name);
+        if let Some(short) = arg.get_short() {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if let Some(long) = arg.get_long() {
+            line.push_str(&format!(" -l {}", long));
+        }
+        if arg.is_set(ArgSettings::TakesValue) {
+            line.push_str(" -r");
+        }
+        line.push_str(&format!(" -d '{}'\n", escape(arg.get_about().unwrap_or_default())));
+        script.push_str(&line);
+    }
+    script
+}
+
+fn powershell(app: &App, name: &str) -> String {
+    let mut words: Vec<String> = visible_subcommands(app).map(|s| s.get_name().to_string()).collect();
+    words.extend(visible_args(app).flat_map(flags));
+    let words: Vec<String> = words.iter().map(|w| format!("'{}'", w)).collect();
+    let mut script = format!("Register-ArgumentCompleter -Native -CommandName {} -ScriptBlock {{\n", name);
+    script.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    script.push_str(&format!("    @({}) |\n", words.join(", ")));
+    script.push_str("        Where-Object { $_ -like \"$wordToComplete*\" } |\n");
+    script.push_str(
+        "        ForEach-Object { [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterName', $_) }\n",
+    );
+    script.push_str("}\n");
+    script
+}
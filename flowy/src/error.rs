@@ -0,0 +1,49 @@
+// THIS MODULE HOLDS THE ERRORS SPECIFIC TO FLOWY
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Errors flowy returns for common mistakes, so that they can be told apart
+pub enum FlowyError {
+    /// The wallpaper path points to a file rather than a folder
+    NotADirectory(PathBuf),
+    /// The wallpaper folder doesn't exist
+    DirectoryNotFound(PathBuf),
+    /// The wallpaper folder has no wallpapers
+    NoWallpapers(PathBuf),
+    /// The wallpaper folder has no wallpapers with the tag, e.g. DAY, in their name
+    NoTaggedWallpapers(PathBuf, String),
+}
+
+impl fmt::Display for FlowyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlowyError::NotADirectory(path) => write!(
+                f,
+                "{} is a file, flowy needs the folder containing your wallpapers",
+                path.display()
+            ),
+            FlowyError::DirectoryNotFound(path) => {
+                write!(f, "The folder {} does not exist", path.display())
+            }
+            FlowyError::NoWallpapers(path) => {
+                write!(f, "No wallpapers found in {}", path.display())
+            }
+            FlowyError::NoTaggedWallpapers(path, tag) => write!(
+                f,
+                "No wallpapers with {} in their name found in {}",
+                tag,
+                path.display()
+            ),
+        }
+    }
+}
+
+// main prints the error it returns with Debug, so this is the message too
+impl fmt::Debug for FlowyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error for FlowyError {}
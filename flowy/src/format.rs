@@ -0,0 +1,45 @@
+// THIS MODULE HANDLES THE FILE FORMATS OF THE CONFIG
+use std::path::Path;
+use std::str::FromStr;
+
+/// File format of the config
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Json,
+    Yaml,
+}
+
+/// Config file names flowy looks for, in order
+pub(crate) const CONFIG_FILES: &[&str] = &["config.toml", "config.json", "config.yaml", "config.yml"];
+
+impl ConfigFormat {
+    /// Returns the format of a file from its extension, TOML if it isn't known
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        extension.parse().unwrap_or_default()
+    }
+
+    /// Returns the extension of files in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            _ => Err(format!("Unknown config format {}, expected toml, json or yaml", s)),
+        }
+    }
+}
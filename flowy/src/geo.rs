@@ -0,0 +1,31 @@
+// THIS MODULE FINDS THE APPROXIMATE LOCATION OF THE MACHINE
+use log::info;
+use std::error::Error;
+use std::time::Duration;
+
+/// Service returning the location of the IP address a request comes from
+const GEOLOCATION_URL: &str = "https://ipapi.co/json/";
+/// How long the lookup may take before giving up
+const GEOLOCATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the approximate latitude and longitude of this machine,
+/// looked up from its public IP address.
+///
+/// This sends a request to a third party, so it is only done when asked for.
+pub fn geolocate() -> Result<(f64, f64), Box<dyn Error>> {
+    info!("Looking up your location from your IP address at {}", GEOLOCATION_URL);
+    let agent = ureq::AgentBuilder::new().timeout(GEOLOCATION_TIMEOUT).build();
+    let reply: serde_json::Value = agent.get(GEOLOCATION_URL).call()?.into_json()?;
+    if let Some(reason) = reply["reason"].as_str() {
+        return Err(reason.into());
+    }
+    let lat = reply["latitude"].as_f64().ok_or("The reply has no latitude")?;
+    let long = reply["longitude"].as_f64().ok_or("The reply has no longitude")?;
+    let place: Vec<_> = ["city", "region", "country_name"]
+        .iter()
+        .filter_map(|key| reply[key].as_str())
+        .filter(|name| !name.is_empty())
+        .collect();
+    info!("Detected location: {} (Lat: {} Long: {})", place.join(", "), lat, long);
+    Ok((lat, long))
+}
@@ -0,0 +1,261 @@
+// THIS MODULE HANDLES ANALYSIS OF THE WALLPAPER IMAGES
+use crate::cache::get_cache_dir;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Side of the thumbnail the colours are averaged over
+const THUMBNAIL_SIZE: u32 = 64;
+
+/// A cached warmth value along with the modification time of the image it belongs to
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedWarmth {
+    mtime: u64,
+    warmth: f64,
+}
+
+/// Returns the path where computed warmth values are cached
+fn get_cache_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut cache_path = get_cache_dir()?;
+    cache_path.push("color_temp.toml");
+    Ok(cache_path)
+}
+
+/// Returns the filesystem path of a wallpaper as stored in the config
+pub(crate) fn file_path(wall: &str) -> PathBuf {
+    PathBuf::from(wallpaper_rs::from_file_uri(wall))
+}
+
+/// Returns when a file was last modified, in seconds since the epoch
+fn modified_secs(path: &Path) -> Result<u64, Box<dyn Error>> {
+    Ok(std::fs::metadata(path)?.modified()?.duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Whether the format of an image, going by its extension, can be read here.
+/// Formats such as AVIF and HEIC can still be shown by the desktop.
+fn can_decode(path: &Path) -> bool {
+    image::ImageFormat::from_path(path).is_ok_and(|f| f.reading_enabled())
+}
+
+/// Whether images with the given extension can be read here, and so converted
+pub(crate) fn can_decode_extension(extension: &str) -> bool {
+    image::ImageFormat::from_extension(extension).is_some_and(|f| f.reading_enabled())
+}
+
+/// Returns why an image file can't be shown, or `None` if it looks fine.
+/// Only its size and header are read, so this is quick even for large images.
+/// Formats which can't be read here are only checked for being empty.
+fn check_image(path: &Path) -> Option<String> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.len() == 0 => return Some("the file is empty".to_string()),
+        Ok(_) => (),
+        Err(e) => return Some(e.to_string()),
+    }
+    if !can_decode(path) {
+        return None;
+    }
+    let reader = match image::ImageReader::open(path).and_then(|r| r.with_guessed_format()) {
+        Ok(reader) => reader,
+        Err(e) => return Some(e.to_string()),
+    };
+    if reader.format().is_none() {
+        return Some("not a known image format".to_string());
+    }
+    reader.into_dimensions().err().map(|e| e.to_string())
+}
+
+/// Drops the wallpapers which are empty or not images, e.g. truncated
+/// downloads, warning about each and about how many were skipped
+pub(crate) fn validate(walls: Vec<String>) -> Vec<String> {
+    let total = walls.len();
+    let valid: Vec<String> = walls
+        .into_iter()
+        .filter(|wall| match check_image(&file_path(wall)) {
+            Some(problem) => {
+                log::warn!("Skipping {}: {}", wall, problem);
+                false
+            }
+            None => true,
+        })
+        .collect();
+    if valid.len() < total {
+        log::warn!("Skipped {} of {} wallpapers which are not valid images", total - valid.len(), total);
+    }
+    valid
+}
+
+/// Returns PNG copies of the wallpapers `unsupported` picks, keeping the others.
+/// Copies are cached by path and modification time, so each image is converted once.
+/// Images which can't be read here are kept as they are, with a warning.
+pub(crate) fn convert_unsupported(
+    walls: Vec<String>,
+    unsupported: impl Fn(&str) -> bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut convert_dir = get_cache_dir()?;
+    convert_dir.push("converted");
+    std::fs::create_dir_all(&convert_dir)?;
+
+    let (mut converted, mut reused, mut skipped) = (0, 0, 0);
+    let mut result = Vec::with_capacity(walls.len());
+    for wall in walls {
+        let path = file_path(&wall);
+        if !unsupported(&wall) {
+            result.push(wall);
+            continue;
+        }
+        if !can_decode(&path) {
+            log::warn!("Cannot convert {}: its format can't be read here", wall);
+            skipped += 1;
+            result.push(wall);
+            continue;
+        }
+        let mtime = modified_secs(&path)?;
+        let mut hasher = DefaultHasher::new();
+        (&path, mtime).hash(&mut hasher);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let copy = convert_dir.join(format!("{:016x}-{}.png", hasher.finish(), stem));
+
+        if copy.exists() {
+            reused += 1;
+        } else {
+            // Written under another name first, so an interrupted conversion is not reused
+            let partial = copy.with_extension("png.part");
+            match image::open(&path).and_then(|image| image.save_with_format(&partial, image::ImageFormat::Png)) {
+                Ok(()) => std::fs::rename(&partial, &copy)?,
+                Err(e) => {
+                    log::warn!("Could not convert {}: {}", wall, e);
+                    let _ = std::fs::remove_file(&partial);
+                    skipped += 1;
+                    result.push(wall);
+                    continue;
+                }
+            }
+            converted += 1;
+        }
+        result.push(copy.display().to_string());
+    }
+    if converted + reused > 0 {
+        log::info!(
+            "Using PNG copies of {} wallpapers ({} newly converted) from {}",
+            converted + reused,
+            converted,
+            convert_dir.display()
+        );
+    }
+    if skipped > 0 {
+        log::warn!("Could not convert {} wallpapers, which are used as they are", skipped);
+    }
+
+    Ok(result)
+}
+
+/// Returns the warmth of an image between -1 (blue) and 1 (red),
+/// which is the average difference between the red and blue channels
+fn warmth(path: &Path) -> Result<f64, Box<dyn Error>> {
+    let thumbnail = image::open(path)?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8();
+    let total: f64 = thumbnail
+        .pixels()
+        .map(|p| (p[0] as f64 - p[2] as f64) / 255.0)
+        .sum();
+
+    Ok(total / (thumbnail.width() * thumbnail.height()).max(1) as f64)
+}
+
+/// Orders wallpapers by colour temperature so that warm images are shown around
+/// sunrise and sunset and cool ones around midday and midnight,
+/// assuming the wallpapers are spread evenly over the day.
+///
+/// Warmth values are cached by path and modification time.
+pub fn sort_by_color_temp(walls: Vec<String>) -> Result<Vec<String>, Box<dyn Error>> {
+    let cache_path = get_cache_path()?;
+    let mut cache: BTreeMap<String, CachedWarmth> = std::fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|c| toml::from_str(&c).ok())
+        .unwrap_or_default();
+
+    let mut by_warmth = Vec::with_capacity(walls.len());
+    for wall in walls {
+        let path = file_path(&wall);
+        let mtime = modified_secs(&path)?;
+        let warmth = match cache.get(&wall) {
+            Some(cached) if cached.mtime == mtime => cached.warmth,
+            // Formats which can't be read here are ordered as neutral
+            _ if !can_decode(&path) => 0.0,
+            _ => {
+                let warmth = warmth(&path)?;
+                cache.insert(wall.clone(), CachedWarmth { mtime, warmth });
+                warmth
+            }
+        };
+        by_warmth.push((warmth, wall));
+    }
+    crate::write_atomic(&cache_path, &toml::to_string(&cache)?)?;
+
+    // Target warmth of each slot, peaking at 06:00 and 18:00
+    let len = by_warmth.len();
+    let mut slots: Vec<(f64, usize)> = (0..len)
+        .map(|i| ((2.0 * PI * i as f64 / len as f64).sin().abs(), i))
+        .collect();
+    slots.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // A corrupt cache may hold NaN, which is ordered last instead of panicking
+    by_warmth.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // The coolest image goes to the coolest slot and so on
+    let mut sorted = vec![String::new(); len];
+    for ((_, slot), (_, wall)) in slots.into_iter().zip(by_warmth) {
+        sorted[slot] = wall;
+    }
+
+    Ok(sorted)
+}
+
+/// Returns a wallpaper which blends `from` into `to`, where a `fraction` of 0
+/// is `from` and 1 is `to`. `to` is resized to the size of `from`.
+///
+/// Blended images are cached by the path and modification time of both
+/// images, so the fraction should be coarse.
+pub fn blend(from: &str, to: &str, fraction: f64) -> Result<String, Box<dyn Error>> {
+    let percent = (fraction.clamp(0.0, 1.0) * 100.0).round() as u32;
+    let (from_path, to_path) = (file_path(from), file_path(to));
+    let mut hasher = Sha256::new();
+    for path in [&from_path, &to_path] {
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(modified_secs(path)?.to_le_bytes());
+    }
+    let mut blend_path = get_cache_dir()?;
+    blend_path.push("morph");
+    std::fs::create_dir_all(&blend_path)?;
+    blend_path.push(format!("{:x}-{:03}.png", hasher.finalize(), percent));
+
+    if !blend_path.exists() {
+        let mut base = image::open(&from_path)?.to_rgb8();
+        let (width, height) = base.dimensions();
+        let top = image::open(&to_path)?
+            .resize_exact(width, height, image::imageops::FilterType::Triangle)
+            .to_rgb8();
+        let weight = percent as f64 / 100.0;
+        for (b, t) in base.pixels_mut().zip(top.pixels()) {
+            for c in 0..3 {
+                b[c] = (b[c] as f64 * (1.0 - weight) + t[c] as f64 * weight).round() as u8;
+            }
+        }
+        // Written under another name first, so an interrupted blend is not reused
+        let partial = blend_path.with_extension("png.part");
+        if let Err(e) = base.save_with_format(&partial, image::ImageFormat::Png) {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e.into());
+        }
+        std::fs::rename(&partial, &blend_path)?;
+    }
+
+    Ok(blend_path.display().to_string())
+}
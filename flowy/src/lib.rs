@@ -1,94 +1,801 @@
 // THIS MODULE HANDLES GENERATION OF THE CONFIG FILE
 // AND THE RUNNING OF THE DAEMON
-use chrono::{DateTime, Local, NaiveTime, Utc};
-use directories_next::BaseDirs;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Timelike, Utc};
+use directories_next::{BaseDirs, UserDirs};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
 use std::thread;
-use std::time::Duration;
-use wallpaper_rs::{Desktop, DesktopEnvt};
+use std::time::{Duration, Instant, SystemTime};
+use wallpaper_rs::{Desktop, DesktopEnvt, WallpaperMode};
+mod appearance;
+mod cache;
+mod error;
+mod format;
+mod geo;
+mod images;
+mod logging;
+mod service;
 mod solar;
+mod sort;
 
-/// Basic error handling to ensure
-/// an empty args field does not
-/// crash the app
+pub use appearance::SUPPORTED as APPEARANCE_SUPPORTED;
+pub use cache::{clean_cache, show_cache};
+pub use error::FlowyError;
+pub use format::ConfigFormat;
+pub use geo::geolocate;
+pub use images::sort_by_color_temp;
+pub use logging::{get_log_path, init_logging};
+pub use solar::{solar_elevation, SolarTime, Timetable};
+pub use sort::SortOrder;
+
+/// Times are stored with minute precision, so at most
+/// one wallpaper can be scheduled per minute of the day
+const MAX_WALLPAPERS: usize = 24 * 60;
+
+/// Generates the config file from `dir` if one is given. A `dir` which is
+/// a file, doesn't exist or has no wallpapers is an error.
 pub fn match_dir(dir: Option<&str>) -> Result<(), Box<dyn Error>> {
-    match dir {
-        None => (),
-        Some(dir) => match generate_config(Path::new(dir)) {
-            Ok(_) => println!("Generated config file"),
-            Err(e) => eprintln!("Error generating config file: {}", e),
-        },
+    if let Some(dir) = dir {
+        generate_config(Path::new(dir))?;
+        info!("Generated config file");
     }
 
     Ok(())
 }
 
 /// Stores the times and filepaths as a vector of strings
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Config {
     pub times: Vec<String>,
     pub walls: Vec<String>,
+    /// Wallpapers shown instead of `walls` in dark mode, on desktops with
+    /// a separate dark mode wallpaper such as GNOME. Indexed like `walls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dark_walls: Option<Vec<String>>,
+    /// How wallpapers are scaled: fill, fit, center, stretch or tile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Seconds each wallpaper takes to fade into the next, on desktops which can
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<f64>,
+    /// How `times` were generated. Solar, elevation and phases schedules are
+    /// computed again each day from `lat`, `long` and `solar_dir`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleKind>,
+    /// Location and wallpaper directory of a solar schedule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solar_dir: Option<String>,
+    /// Tags telling day and night wallpapers apart in `solar_dir`, if not DAY and NIGHT
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub night_tag: Option<String>,
+    /// Solar events at which each wallpaper of a phases schedule is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phases: Option<Vec<String>>,
+    /// Wallpapers for individual monitors on KDE, XFCE, macOS and Windows, keyed by the
+    /// monitor's number counting from 0, or its name. Indexed like `walls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_monitor: Option<BTreeMap<String, Vec<String>>>,
+    /// Wallpapers for individual workspaces, keyed by workspace name
+    /// (the activity id on KDE). Each list is indexed like `walls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_workspace: Option<BTreeMap<String, Vec<String>>>,
+}
+
+/// How the times of a config were generated
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleKind {
+    /// Spread over the day, or written by hand
+    Normal,
+    /// Split at sunrise and sunset, see `generate_config_solar`
+    Solar,
+    /// Following the sun's elevation, see `generate_config_elevation`
+    Elevation,
+    /// Set at chosen solar events such as twilight, see `generate_config_phases`
+    Phases,
+}
+
+impl Config {
+    /// Checks that the config can be scheduled, i.e. that there is
+    /// a time of the form HH:MM for every wallpaper
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.times.is_empty() {
+            return Err("The config has no wallpapers".into());
+        }
+        if self.times.len() != self.walls.len() {
+            return Err(format!(
+                "The config has {} times but {} wallpapers",
+                self.times.len(),
+                self.walls.len()
+            )
+            .into());
+        }
+        if let Some(time) = self
+            .times
+            .iter()
+            .find(|t| NaiveTime::parse_from_str(t, "%H:%M").is_err())
+        {
+            return Err(format!("The time {:?} in the config is not of the form HH:MM", time).into());
+        }
+        if let Some(dark_walls) = self.dark_walls.as_ref().filter(|d| d.len() != self.walls.len()) {
+            return Err(format!(
+                "The config has {} wallpapers but {} dark mode wallpapers",
+                self.walls.len(),
+                dark_walls.len()
+            )
+            .into());
+        }
+        self.wallpaper_mode()?;
+        self.wallpaper_transition()?;
+        Ok(())
+    }
+
+    /// Returns the scaling set by `mode`, if any
+    pub fn wallpaper_mode(&self) -> Result<Option<WallpaperMode>, Box<dyn Error>> {
+        Ok(self.mode.as_deref().map(str::parse).transpose()?)
+    }
+
+    /// Returns how long each wallpaper is shown, until the time of the next one.
+    /// The last one is shown until the first time of the next day.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let config = flowy::Config {
+    ///     times: vec!["06:00".into(), "18:00".into(), "22:30".into()],
+    ///     walls: vec!["day.jpg".into(), "evening.jpg".into(), "night.jpg".into()],
+    ///     ..Default::default()
+    /// };
+    /// let hours = |h: f64| Duration::from_secs_f64(h * 3600.0);
+    /// // The night wallpaper wraps past midnight until 06:00
+    /// assert_eq!(config.durations().unwrap(), vec![hours(12.0), hours(4.5), hours(7.5)]);
+    /// ```
+    pub fn durations(&self) -> Result<Vec<Duration>, Box<dyn Error>> {
+        let minutes = self
+            .times
+            .iter()
+            .map(|t| Ok(NaiveTime::parse_from_str(t, "%H:%M")?.num_seconds_from_midnight() / 60))
+            .collect::<Result<Vec<u32>, Box<dyn Error>>>()?;
+        let day = 24 * 60;
+        Ok(minutes
+            .iter()
+            .zip(minutes.iter().cycle().skip(1))
+            .map(|(&start, &end)| match (end + day - start) % day {
+                // A single wallpaper is shown all day
+                0 if minutes.len() == 1 => day,
+                length => length,
+            })
+            .map(|length| Duration::from_secs(length as u64 * 60))
+            .collect())
+    }
+
+    /// Returns the fade set by `transition`, if any
+    pub fn wallpaper_transition(&self) -> Result<Option<Duration>, Box<dyn Error>> {
+        self.transition.map(parse_transition).transpose()
+    }
+
+    /// Turns the `file://` URIs older versions wrote on Linux into plain paths,
+    /// so that configs can be shared with other systems
+    fn normalize_walls(&mut self) {
+        let per_monitor = self.per_monitor.iter_mut().flat_map(|p| p.values_mut());
+        let per_workspace = self.per_workspace.iter_mut().flat_map(|p| p.values_mut());
+        let lists = std::iter::once(&mut self.walls)
+            .chain(self.dark_walls.as_mut())
+            .chain(per_monitor)
+            .chain(per_workspace);
+        for wall in lists.flatten() {
+            *wall = wallpaper_rs::from_file_uri(wall);
+        }
+    }
+}
+
+/// Comments explaining each key of the config, used by `config_template`
+const CONFIG_DOCS: &[(&str, &str)] = &[
+    ("times", "Times at which each wallpaper is set, as HH:MM in the order they are shown"),
+    ("walls", "Absolute paths of the wallpapers to set, one for each time"),
+    ("dark_walls", "Optional. Wallpapers for dark mode on GNOME, one for each time"),
+    ("mode", "Optional. How wallpapers are scaled: fill, fit, center, stretch or tile"),
+    ("transition", "Optional. Seconds each wallpaper fades in for, with swww on Sway and River"),
+    (
+        "[per_monitor]",
+        "Optional. Wallpapers for individual monitors on KDE, XFCE, macOS and Windows, by number\n\
+         # (counting from 0) or name, one for each time",
+    ),
+    (
+        "[per_workspace]",
+        "Optional. Wallpapers for individual workspaces on KDE, by activity id, one for each time",
+    ),
+];
+
+/// Returns a commented example config file
+pub fn config_template() -> Result<String, Box<dyn Error>> {
+    let example = Config {
+        times: vec!["00:00".to_string(), "12:00".to_string()],
+        walls: vec![
+            "/home/me/wallpapers/night.jpg".to_string(),
+            "/home/me/wallpapers/day.jpg".to_string(),
+        ],
+        dark_walls: Some(vec![
+            "/home/me/wallpapers/night-dark.jpg".to_string(),
+            "/home/me/wallpapers/day-dark.jpg".to_string(),
+        ]),
+        mode: Some("fill".to_string()),
+        transition: Some(1.5),
+        per_monitor: Some(
+            vec![(
+                "1".to_string(),
+                vec![
+                    "/home/me/wallpapers/side-night.jpg".to_string(),
+                    "/home/me/wallpapers/side-day.jpg".to_string(),
+                ],
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        per_workspace: Some(
+            vec![(
+                "work".to_string(),
+                vec![
+                    "/home/me/wallpapers/work-night.jpg".to_string(),
+                    "/home/me/wallpapers/work-day.jpg".to_string(),
+                ],
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        ..Default::default()
+    };
+
+    // Putting the comment of each key above it
+    let mut template = String::from("# flowy config file\n\n");
+    for line in toml::to_string(&example)?.lines() {
+        let key = line.split(" =").next().unwrap_or_default();
+        if let Some((_, doc)) = CONFIG_DOCS.iter().find(|(k, _)| *k == key) {
+            template.push_str(&format!("# {}\n", doc));
+        }
+        template.push_str(line);
+        template.push('\n');
+    }
+    Ok(template)
+}
+
+/// Checks that every wallpaper of the config exists and can be read.
+/// Problems are printed and an error is returned if any wallpaper failed.
+pub fn verify_config(config: &Config) -> Result<(), Box<dyn Error>> {
+    config.validate()?;
+    let mut missing = 0;
+    let mut unreadable = 0;
+    for wall in &config.walls {
+        let path = images::file_path(wall);
+        if !path.is_file() {
+            println!("Missing: {}", wall);
+            missing += 1;
+        } else if let Err(e) = std::fs::File::open(path) {
+            println!("Unreadable: {} ({})", wall, e);
+            unreadable += 1;
+        }
+    }
+
+    let total = config.walls.len();
+    println!(
+        "{}/{} wallpapers OK, {} missing, {} unreadable",
+        total - missing - unreadable,
+        total,
+        missing,
+        unreadable
+    );
+    if missing + unreadable > 0 {
+        return Err(format!("{} wallpapers can't be used", missing + unreadable).into());
+    }
+    Ok(())
+}
+
+/// A config and the file it is kept in, for using flowy's schedules from
+/// other programs. Unlike `set_times` nothing runs in the background.
+#[derive(Debug)]
+pub struct Flowy {
+    config_path: PathBuf,
+    config: Config,
+}
+
+impl Flowy {
+    /// Loads the config at `path`, or flowy's own config if `None`
+    pub fn open(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let config_path = config_path_or_default(path)?;
+        let config = read_config(&config_path)?;
+        Ok(Self { config_path, config })
+    }
+
+    /// Spreads the wallpapers in `dir` evenly over the day and writes
+    /// the config to `path`, or flowy's own config if `None`
+    pub fn generate(path: Option<&Path>, dir: &Path) -> Result<Self, Box<dyn Error>> {
+        check_dir(dir)?;
+        let walls = get_dir(dir, "")?;
+        if walls.is_empty() {
+            return Err(FlowyError::NoWallpapers(dir.to_path_buf()).into());
+        }
+        info!("<---- Normal Mode ---->");
+        let config = even_schedule(walls)?;
+        let config_path = config_path_or_default(path)?;
+        write_config_to(&config_path, &config)?;
+        Ok(Self { config_path, config })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Reads the config file again, e.g. after it was edited.
+    /// The loaded config is kept if the file can't be read.
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        self.config = read_config(&self.config_path)?;
+        Ok(())
+    }
+
+    /// Returns the index of the wallpaper the schedule shows at `time`
+    pub fn current_index(&self, time: NaiveTime) -> Result<usize, Box<dyn Error>> {
+        get_current_wallpaper_idx(&self.config.times, time)
+    }
+
+    /// Sets the wallpaper the schedule shows now once, returning its index
+    pub fn apply_once(&self) -> Result<usize, Box<dyn Error>> {
+        let index = self.current_index(Local::now().time())?;
+        apply_index(&self.config, index)?;
+        Ok(index)
+    }
 }
 
 /// Creates a new instance of struct Config and returns it
 pub fn get_config() -> Result<Config, Box<dyn Error>> {
+    Ok(Flowy::open(None)?.config)
+}
+
+/// Reads and checks the config file at `config_path`
+fn read_config(config_path: &Path) -> Result<Config, Box<dyn Error>> {
+    let config_file = match std::fs::read_to_string(config_path) {
+        Ok(config_file) => config_file,
+        // Most likely flowy has not been set up yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!(
+                "No config found at {}, run flowy init or flowy --dir <DIR> first",
+                config_path.display()
+            )
+            .into())
+        }
+        Err(e) => return Err(format!("Could not read the config {}: {}", config_path.display(), e).into()),
+    };
+    let origin = config_path.display().to_string();
+    let config = parse_config(&config_file, ConfigFormat::from_path(config_path), &origin)?;
+    config
+        .validate()
+        .map_err(|e| format!("Invalid config {}: {}", origin, e))?;
+    Ok(config)
+}
+
+/// Parses a config file. A TOML config may be a section of a shared file.
+/// `origin` names the file in errors.
+fn parse_config(config_file: &str, format: ConfigFormat, origin: &str) -> Result<Config, Box<dyn Error>> {
+    let mut config: Config = match format {
+        ConfigFormat::Toml => {
+            let mut toml_value: toml::Value = toml::from_str(config_file)?;
+            if let Some(section) = config_section(&toml_value) {
+                toml_value = toml_value
+                    .get(&section)
+                    .cloned()
+                    .ok_or_else(|| format!("No [{}] section in {}", section, origin))?;
+            }
+            toml_value.try_into()?
+        }
+        ConfigFormat::Json => serde_json::from_str(config_file)?,
+        ConfigFormat::Yaml => serde_yaml::from_str(config_file)?,
+    };
+    config.normalize_walls();
+
+    Ok(config)
+}
+
+/// Makes `contents` the config file, after checking that it is a valid
+/// config and that its wallpapers exist. An existing config file is
+/// backed up, e.g. to `config.toml.bak`. `origin` names the new config in
+/// errors, and its extension gives the format.
+pub fn install_config(contents: &str, origin: &str) -> Result<(), Box<dyn Error>> {
+    let format = ConfigFormat::from_path(Path::new(origin));
+    let config = parse_config(contents, format, origin)?;
+    config.validate()?;
+    check_walls_exist(&config.walls, origin)?;
+
     let config_path = get_config_path()?;
-    let toml_file = std::fs::read_to_string(&config_path)?;
-    let toml_data: Config = toml::from_str(&toml_file)?;
+    if config_path.exists() {
+        let mut backup = config_path.clone().into_os_string();
+        backup.push(".bak");
+        std::fs::rename(&config_path, &backup)?;
+        info!("Backed up the old config to {}", Path::new(&backup).display());
+    }
+    // A config given with --config keeps its name
+    let new_path = match CONFIG_PATH.lock().unwrap().clone() {
+        Some(path) => path,
+        None => config_path.with_file_name(format!("config.{}", format.extension())),
+    };
+    write_atomic(&new_path, contents)
+}
+
+/// Returns the wallpapers of another config file so that they can be
+/// given new times. Every wallpaper must exist.
+pub fn get_walls_from_config(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let origin = path.display().to_string();
+    let config = parse_config(&std::fs::read_to_string(path)?, ConfigFormat::from_path(path), &origin)?;
+    if config.walls.is_empty() {
+        return Err(format!("No wallpapers in {}", path.display()).into());
+    }
+    check_walls_exist(&config.walls, &origin)?;
+
+    Ok(config.walls)
+}
+
+/// Checks that every wallpaper of a config named `origin` exists
+fn check_walls_exist(walls: &[String], origin: &str) -> Result<(), Box<dyn Error>> {
+    for wall in walls {
+        if !images::file_path(wall).is_file() {
+            return Err(format!("Wallpaper {} in {} does not exist", wall, origin).into());
+        }
+    }
+    Ok(())
+}
+
+/// Table of a shared config file which holds flowy's config
+static CONFIG_SECTION: Mutex<Option<String>> = Mutex::new(None);
+
+/// Makes flowy read and write its config in the `[name]` table of the
+/// config file, so that the file can be shared with other tools.
+pub fn set_config_section(name: &str) {
+    *CONFIG_SECTION.lock().unwrap() = Some(name.to_string());
+}
+
+/// Returns the table holding flowy's config, if it isn't at the top level.
+/// Without `set_config_section`, a `[flowy]` table is used when there are no top level times.
+fn config_section(toml_value: &toml::Value) -> Option<String> {
+    if let Some(section) = CONFIG_SECTION.lock().unwrap().clone() {
+        return Some(section);
+    }
+    if toml_value.get("times").is_none() && toml_value.get("flowy").is_some() {
+        return Some("flowy".to_string());
+    }
+    None
+}
+
+/// Writes the config to the config file in its format. When the config lives
+/// in a section of a shared TOML file, the other tables of the file are kept.
+fn write_config(config: &Config) -> Result<(), Box<dyn Error>> {
+    write_config_to(&get_config_path()?, config)
+}
+
+/// Writes the config file at `config_path` in the format of its extension
+fn write_config_to(config_path: &Path, config: &Config) -> Result<(), Box<dyn Error>> {
+    match ConfigFormat::from_path(config_path) {
+        ConfigFormat::Toml => (),
+        ConfigFormat::Json => return write_atomic(config_path, &(serde_json::to_string_pretty(config)? + "\n")),
+        ConfigFormat::Yaml => return write_atomic(config_path, &serde_yaml::to_string(config)?),
+    }
+    let existing: toml::Value = std::fs::read_to_string(config_path)
+        .ok()
+        .and_then(|f| toml::from_str(&f).ok())
+        .unwrap_or_else(|| toml::Value::Table(Default::default()));
+
+    let toml_string = match (config_section(&existing), existing) {
+        (Some(section), toml::Value::Table(mut table)) => {
+            table.insert(section, toml::Value::try_from(config)?);
+            toml::to_string(&table)?
+        }
+        _ => toml::to_string(config)?,
+    };
+    write_atomic(config_path, &toml_string)
+}
 
-    Ok(toml_data)
+/// Returns the wallpapers of an existing wallpaper set made for another
+/// slideshow mechanism, in their implied order.
+///
+/// Supported are KDE wallpaper packages (images in `contents/images`)
+/// and KDE slideshow folders, which are plain folders of images.
+pub fn import_walls(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("heic")) {
+        return Err("Importing macOS dynamic wallpapers is not supported yet".into());
+    }
+    check_dir(path)?;
+
+    // A KDE wallpaper package keeps its images in contents/images
+    let package_images = path.join("contents").join("images");
+    let walls = if package_images.is_dir() {
+        info!("Importing KDE wallpaper package {}", path.display());
+        get_dir(&package_images, "")?
+    } else {
+        info!("Importing slideshow folder {}", path.display());
+        get_dir(path, "")?
+    };
+    if walls.is_empty() {
+        return Err(format!("No wallpapers found in {}", path.display()).into());
+    }
+
+    Ok(walls)
+}
+
+/// Writes the schedule held by a config to a standalone file
+/// so that it can be consumed by other tools.
+///
+/// Each line is of the form `HH:MM=path`, in the same order as in the config.
+/// Lines starting with `#` are comments.
+pub fn dump_schedule(config: &Config, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut schedule = String::from("# flowy schedule: HH:MM=path\n");
+    for (time, wall) in config.times.iter().zip(config.walls.iter()) {
+        schedule.push_str(&format!("{}={}\n", time, wall));
+    }
+    write_atomic(path, &schedule)?;
+
+    Ok(())
+}
+
+/// Checks that the wallpaper path is an existing directory
+fn check_dir(path: &Path) -> Result<(), FlowyError> {
+    if path.is_dir() {
+        Ok(())
+    } else if path.exists() {
+        Err(FlowyError::NotADirectory(path.to_path_buf()))
+    } else {
+        Err(FlowyError::DirectoryNotFound(path.to_path_buf()))
+    }
+}
+
+/// Extensions of the files used as wallpapers, compared ignoring case
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "avif", "heic", "heif", "bmp", "tif", "tiff"];
+
+/// Checks if a path is an image file flowy can use as a wallpaper
+fn is_image(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| IMAGE_EXTENSIONS.iter().any(|i| e.eq_ignore_ascii_case(i)))
+}
+
+/// How wallpaper directories are read
+#[derive(Debug, Default, Clone)]
+pub struct ScanOptions {
+    /// Order of the wallpapers
+    pub sort: SortOrder,
+    /// Stores the targets of symlinks instead of the links
+    pub resolve_symlinks: bool,
+    /// Also reads the subdirectories
+    pub recursive: bool,
+    /// Seed of the random order, which is different each time without one
+    pub seed: Option<u64>,
+    /// Skips files which are empty or not images
+    pub validate_images: bool,
+    /// Uses PNG copies of images in formats the desktop can't show
+    pub convert: bool,
+}
+
+/// Options used by `get_dir`
+static SCAN_OPTIONS: Mutex<ScanOptions> = Mutex::new(ScanOptions {
+    sort: SortOrder::Natural,
+    resolve_symlinks: false,
+    recursive: false,
+    seed: None,
+    validate_images: false,
+    convert: false,
+});
+
+/// Sets how wallpaper directories are read when generating a config
+pub fn set_scan_options(options: ScanOptions) {
+    *SCAN_OPTIONS.lock().unwrap() = options;
+}
+
+/// Returns the entries of a directory, and with `recursive` those of
+/// its subdirectories. Hidden and symlinked subdirectories are not entered.
+fn read_paths(path: &Path, recursive: bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if recursive && !hidden && entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+            paths.push(entry.path());
+        }
+    }
+    Ok(paths)
 }
 
 /// Returns the contents of a given dir
 pub fn get_dir(path: &Path, solar_filter: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let mut files: Vec<String> = std::fs::read_dir(path)?
-        .into_iter()
-        .map(|x| x.unwrap().path().display().to_string())
-        .filter(|y| y.contains(solar_filter))
-        .collect();
+    let options = SCAN_OPTIONS.lock().unwrap().clone();
+    // Configs store absolute paths, so that they work from any directory
+    let path = &std::path::absolute(path)?;
+    let mut paths = read_paths(path, options.recursive)?;
 
-    // Appens file:// to the start of each item
-    if cfg!(target_os = "linux") {
-        files = files
+    // Storing the targets of symlinks, which not every desktop follows
+    if options.resolve_symlinks {
+        paths = paths
             .into_iter()
-            .map(|y| "file://".to_string() + &y)
-            .filter(|y| y.contains(solar_filter))
+            .filter_map(|p| match p.canonicalize() {
+                Ok(target) => Some(target),
+                Err(e) => {
+                    warn!("Skipping broken symlink {}: {}", p.display(), e);
+                    None
+                }
+            })
             .collect();
     }
 
-    if cfg!(target_os = "macos") {
-        files = files.into_iter()
-        .filter(|y| y.contains(solar_filter))
+    // Skipping folders and files such as .DS_Store or Thumbs.db
+    // The solar tag is looked for below `path` only, so that a folder
+    // such as /home/holiday does not tag every wallpaper
+    let mut files: Vec<String> = paths
+        .iter()
+        .filter(|x| is_image(x))
+        .filter(|x| has_tag(&x.strip_prefix(path).unwrap_or(x).display().to_string(), solar_filter))
+        .map(|x| x.display().to_string())
         .collect();
+    if options.validate_images {
+        files = images::validate(files);
     }
+
     // The read_dir iterator returns in an arbitrary manner
     // Sorted so that the images are viewed at the right time
-    // Naming Mechanism - 00, 01, 02..
-    files.sort();
+    // Naming Mechanism - 00, 01, 02.. or 1, 2, .. 10
+    sort::sort_walls(&mut files, options.sort, options.seed)?;
+    // Without a desktop, e.g. over SSH, nothing is known about the formats it shows
+    // Converted after sorting, as the copies are not named like the originals
+    if let Ok(desktop_envt) = DesktopEnvt::new() {
+        if options.convert {
+            files = images::convert_unsupported(files, |wall| unsupported_format(&desktop_envt, wall).is_some())?;
+        }
+        warn_unsupported_formats(&desktop_envt, &files);
+    }
     Ok(files)
 }
 
-/// Does esentially the same thing as generate_config
+/// Warns about wallpapers in formats the desktop is known not to show,
+/// as setting them may do nothing without an error
+fn warn_unsupported_formats(desktop_envt: &DesktopEnvt, walls: &[String]) {
+    let mut unsupported: BTreeMap<String, usize> = BTreeMap::new();
+    for extension in walls.iter().filter_map(|wall| unsupported_format(desktop_envt, wall)) {
+        *unsupported.entry(extension).or_default() += 1;
+    }
+    let name = match (cfg!(target_os = "linux"), desktop_envt.tool()) {
+        (true, Some(tool)) => format!("{:?} with {}", desktop_envt, tool),
+        (true, None) => format!("{:?}", desktop_envt),
+        (false, _) => std::env::consts::OS.to_string(),
+    };
+    for (extension, count) in unsupported {
+        let wallpapers = if count == 1 { "wallpaper is" } else { "wallpapers are" };
+        let hint = if images::can_decode_extension(&extension) {
+            "Convert them to PNG or JPEG, or pass --convert"
+        } else {
+            "Convert them to PNG or JPEG"
+        };
+        warn!(
+            "{} {} {}, which {} may not be able to show. {}",
+            count,
+            wallpapers,
+            extension.to_uppercase(),
+            name,
+            hint
+        );
+    }
+}
+
+/// Returns the extension of a wallpaper, in lower case, if the desktop is known not to show it
+fn unsupported_format(desktop_envt: &DesktopEnvt, wall: &str) -> Option<String> {
+    let extension = Path::new(wall).extension()?.to_str()?.to_lowercase();
+    Some(extension).filter(|e| desktop_envt.unsupported_formats().contains(&&e[..]))
+}
+
+/// Default tag of day wallpapers in solar mode
+pub const DAY_TAG: &str = "DAY";
+/// Default tag of night wallpapers in solar mode
+pub const NIGHT_TAG: &str = "NIGHT";
+
+/// Whether the name of a wallpaper contains the tag, ignoring case
+pub fn has_tag(wall: &str, tag: &str) -> bool {
+    wall.to_lowercase().contains(&tag.to_lowercase())
+}
+
+/// Does essentially the same thing as generate_config
 /// Only runs when sunrise and sunset times
 /// need to be accounted for
-/// Takes lat and long of a location along with the wallpaper path
-pub fn generate_config_solar(path: &Path, lat: f64, long: f64) -> Result<(), Box<dyn Error>> {
-    println!("<---- Solar Mode ---->");
-    println!("Lat: {} Long: {}", &lat, &long);
+/// Takes lat and long of a location along with the wallpaper path,
+/// and the tags of day and night wallpapers
+pub fn generate_config_solar(
+    path: &Path,
+    lat: f64,
+    long: f64,
+    day_tag: &str,
+    night_tag: &str,
+) -> Result<(), Box<dyn Error>> {
+    write_config(&solar_dir_config(path, lat, long, day_tag, night_tag)?)
+}
+
+/// Returns the solar schedule for today of the wallpapers in `path`
+fn solar_dir_config(path: &Path, lat: f64, long: f64, day_tag: &str, night_tag: &str) -> Result<Config, Box<dyn Error>> {
+    check_dir(path)?;
     // Checking for the night and day prefix
-    let mut day_walls = get_dir(path, "DAY")?;
-    let night_walls = get_dir(path, "NIGHT")?;
+    let day_walls = get_dir(path, day_tag)?;
+    let night_walls = get_dir(path, night_tag)?;
+    if day_walls.is_empty() {
+        return Err(FlowyError::NoTaggedWallpapers(path.to_path_buf(), day_tag.to_string()).into());
+    }
+    if night_walls.is_empty() {
+        return Err(FlowyError::NoTaggedWallpapers(path.to_path_buf(), night_tag.to_string()).into());
+    }
+    let mut config = solar_config(day_walls, night_walls, lat, long)?;
+    config.solar_dir = Some(path.canonicalize()?.display().to_string());
+    config.day_tag = Some(day_tag.to_string()).filter(|t| t != DAY_TAG);
+    config.night_tag = Some(night_tag.to_string()).filter(|t| t != NIGHT_TAG);
+    Ok(config)
+}
+
+/// Generates the solar config file from lists of day and night wallpapers
+pub fn generate_config_solar_from_walls(
+    day_walls: Vec<String>,
+    night_walls: Vec<String>,
+    lat: f64,
+    long: f64,
+) -> Result<(), Box<dyn Error>> {
+    write_config(&solar_config(day_walls, night_walls, lat, long)?)
+}
+
+/// Returns the solar schedule for today
+fn solar_config(
+    mut day_walls: Vec<String>,
+    night_walls: Vec<String>,
+    lat: f64,
+    long: f64,
+) -> Result<Config, Box<dyn Error>> {
+    info!("<---- Solar Mode ---->");
+    info!("Lat: {} Long: {}", &lat, &long);
+    if day_walls.is_empty() || night_walls.is_empty() {
+        return Err("Solar mode needs both DAY and NIGHT wallpapers".into());
+    }
     let unixtime = DateTime::timestamp(&Utc::now()) as f64;
     // Creating solar table based on time, lat, long
     let tt = solar::Timetable::new(unixtime, lat, long);
-    let (sunrise, sunset) = tt.get_sunrise_sunset();
+    let (sunrise, sunset) = match tt.get_sunrise_sunset() {
+        Some(times) => times,
+        // The sun does not rise or set today, so only one set of wallpapers is shown
+        None => return polar_config(day_walls, night_walls, tt.sun_up_at_noon(), lat, long),
+    };
 
     // Day length in seconds
     let day_len = (sunset - sunrise) % 86400;
     // Night length in seconds
     let night_len = (86400 - day_len) % 86400;
+    // Every wallpaper needs its own minute within its part of the day
+    if day_walls.len() as i64 > day_len / 60 || night_walls.len() as i64 > night_len / 60 {
+        return Err(format!(
+            "Too many wallpapers for a {} minute day and {} minute night",
+            day_len / 60,
+            night_len / 60
+        )
+        .into());
+    }
     // Offset in seconds for each wallpaper change during the day
     let day_div = day_len / (day_walls.len()) as i64;
     // Offset in seconds for each wallpaper change during the night
@@ -109,34 +816,389 @@ pub fn generate_config_solar(path: &Path, lat: f64, long: f64) -> Result<(), Box
     }
     // Loading all the night paths to day paths
     day_walls.extend(night_walls);
-    let config = Config {
+    Ok(Config {
         times,
         walls: day_walls,
+        schedule: Some(ScheduleKind::Solar),
+        lat: Some(lat),
+        long: Some(long),
+        ..Default::default()
+    })
+}
+
+/// Returns the solar schedule for a day of polar day or polar night,
+/// spreading the day or night wallpapers evenly over the whole day
+fn polar_config(
+    day_walls: Vec<String>,
+    night_walls: Vec<String>,
+    polar_day: bool,
+    lat: f64,
+    long: f64,
+) -> Result<Config, Box<dyn Error>> {
+    let walls = if polar_day {
+        info!("The sun does not set today, showing only DAY wallpapers");
+        day_walls
+    } else {
+        info!("The sun does not rise today, showing only NIGHT wallpapers");
+        night_walls
     };
-    // Writing times and paths to config.toml
-    let toml_string = toml::to_string(&config)?;
-    std::fs::write(&get_config_path()?, toml_string)?;
+    Ok(Config {
+        schedule: Some(ScheduleKind::Solar),
+        lat: Some(lat),
+        long: Some(long),
+        ..even_schedule(walls)?
+    })
+}
 
-    Ok(())
+/// Names of the solar events accepted by `--phases`
+pub const PHASE_NAMES: &[&str] = &[
+    "astro-dawn",
+    "naut-dawn",
+    "civil-dawn",
+    "sunrise",
+    "noon",
+    "sunset",
+    "civil-dusk",
+    "naut-dusk",
+    "astro-dusk",
+    "midnight",
+];
+
+/// Parses a list of solar events such as `civil-dawn,sunrise,noon,sunset`
+pub fn parse_phases(list: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let phases: Vec<String> = list.split(',').map(|p| p.trim().to_lowercase()).collect();
+    if let Some(phase) = phases.iter().find(|p| solar::SolarTime::from_name(p).is_none()) {
+        return Err(format!("Unknown phase {:?}, expected one of {}", phase, PHASE_NAMES.join(", ")).into());
+    }
+    Ok(phases)
+}
+
+/// Generates the config file from the wallpapers in `path`, setting them
+/// in order at the solar events `phases`, e.g. astro-dawn, civil-dawn and sunrise
+pub fn generate_config_phases(path: &Path, lat: f64, long: f64, phases: &[String]) -> Result<(), Box<dyn Error>> {
+    write_config(&phases_config(path, lat, long, phases)?)
+}
+
+/// Returns the phases schedule for today of the wallpapers in `path`
+fn phases_config(path: &Path, lat: f64, long: f64, phases: &[String]) -> Result<Config, Box<dyn Error>> {
+    check_dir(path)?;
+    let walls = get_dir(path, "")?;
+    if walls.is_empty() {
+        return Err(FlowyError::NoWallpapers(path.to_path_buf()).into());
+    }
+    if walls.len() != phases.len() {
+        return Err(format!("Found {} wallpapers for {} phases, there must be one for each phase", walls.len(), phases.len()).into());
+    }
+    info!("<---- Phases Mode ---->");
+    info!("Lat: {} Long: {}", &lat, &long);
+    let unixtime = DateTime::timestamp(&Utc::now()) as f64;
+    let tt = solar::Timetable::new(unixtime, lat, long);
+    let mut times = Vec::new();
+    for phase in phases {
+        let event = solar::SolarTime::from_name(phase).ok_or_else(|| format!("Unknown phase {:?}", phase))?;
+        // The sun does not reach every elevation near the poles
+        let time = tt
+            .get(&event)
+            .ok_or_else(|| format!("There is no {} today at Lat: {} Long: {}", phase, lat, long))?;
+        times.push(solar::unix_to_local(time.round() as i64).format("%H:%M").to_string());
+    }
+    let config = Config {
+        times,
+        walls,
+        schedule: Some(ScheduleKind::Phases),
+        lat: Some(lat),
+        long: Some(long),
+        solar_dir: Some(path.canonicalize()?.display().to_string()),
+        phases: Some(phases.to_vec()),
+        ..Default::default()
+    };
+    Ok(config)
+}
+
+/// Generates the config file from wallpapers which are steps of brightness,
+/// darkest first. The range of elevations the sun reaches today is split into
+/// one band per wallpaper, and each wallpaper is shown while the sun is in its band.
+pub fn generate_config_elevation(path: &Path, lat: f64, long: f64) -> Result<(), Box<dyn Error>> {
+    write_config(&elevation_config(path, lat, long)?)
+}
+
+/// Returns the elevation schedule for today of the wallpapers in `path`
+fn elevation_config(path: &Path, lat: f64, long: f64) -> Result<Config, Box<dyn Error>> {
+    check_dir(path)?;
+    let walls = get_dir(path, "")?;
+    if walls.is_empty() {
+        return Err(FlowyError::NoWallpapers(path.to_path_buf()).into());
+    }
+    info!("<---- Elevation Mode ---->");
+    info!("Lat: {} Long: {}", &lat, &long);
+    let midnight = Local::today().and_hms(0, 0, 0).timestamp();
+    let mut config = elevation_schedule(walls, lat, long, midnight)?;
+    config.schedule = Some(ScheduleKind::Elevation);
+    config.lat = Some(lat);
+    config.long = Some(long);
+    config.solar_dir = Some(path.canonicalize()?.display().to_string());
+    Ok(config)
+}
+
+/// Builds the schedule of `generate_config_elevation` for the day starting at `midnight`
+fn elevation_schedule(walls: Vec<String>, lat: f64, long: f64, midnight: i64) -> Result<Config, Box<dyn Error>> {
+    if walls.is_empty() {
+        return Err("No wallpapers found".into());
+    }
+    // The sun's elevation at every minute of the day
+    let elevations: Vec<f64> = (0..24 * 60)
+        .map(|minute| solar::solar_elevation((midnight + minute * 60) as f64, lat, long))
+        .collect();
+    let lowest = elevations.iter().copied().fold(f64::INFINITY, f64::min);
+    let highest = elevations.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    // Near the poles the elevation barely changes in a day, so the bands
+    // are kept from collapsing to nothing
+    let band = ((highest - lowest) / walls.len() as f64).max(f64::EPSILON);
+
+    let mut config = Config::default();
+    let mut last_step = None;
+    for (minute, elevation) in elevations.iter().enumerate() {
+        // Clamped so that the highest elevation falls in the last band
+        let step = (((elevation - lowest) / band) as usize).min(walls.len() - 1);
+        if last_step != Some(step) {
+            last_step = Some(step);
+            config.times.push(format!("{:02}:{:02}", minute / 60, minute % 60));
+            config.walls.push(walls[step].clone());
+        }
+    }
+    Ok(config)
 }
 
 /// Generates the config file. Takes the wallpaper folder path as args.
 pub fn generate_config(path: &Path) -> Result<(), Box<dyn Error>> {
-    println!("<---- Normal Mode ---->");
-    let walls = get_dir(path, "")?;
+    Flowy::generate(None, path)?;
+    Ok(())
+}
+
+/// Folders `init` looks for wallpapers in, most specific first: `Wallpapers`
+/// and `wallpapers` in the Pictures folder, then the Pictures folder itself
+fn default_wall_dirs() -> Vec<PathBuf> {
+    let user_dirs = match UserDirs::new() {
+        Some(user_dirs) => user_dirs,
+        None => return Vec::new(),
+    };
+    // Without an XDG user-dirs file the Pictures folder is not known on Linux
+    let pictures = match user_dirs.picture_dir() {
+        Some(pictures) => pictures.to_path_buf(),
+        None => user_dirs.home_dir().join("Pictures"),
+    };
+    vec![pictures.join("Wallpapers"), pictures.join("wallpapers"), pictures]
+}
+
+/// Generates a config from the first default wallpaper folder with wallpapers,
+/// or prints how to make one if there is none.
+/// An existing config is only replaced with `force`.
+pub fn init(force: bool) -> Result<(), Box<dyn Error>> {
+    let config_path = get_config_path()?;
+    if config_path.exists() && !force {
+        return Err(format!(
+            "A config already exists at {}, pass --force to replace it",
+            config_path.display()
+        )
+        .into());
+    }
+
+    let candidates = default_wall_dirs();
+    for dir in &candidates {
+        match Flowy::generate(None, dir) {
+            Ok(flowy) => {
+                println!(
+                    "Spread {} wallpapers from {} over the day in {}",
+                    flowy.config().walls.len(),
+                    dir.display(),
+                    flowy.config_path().display()
+                );
+                println!("Run flowy to start changing the wallpaper, or flowy --dir <DIR> to use another folder");
+                return Ok(());
+            }
+            Err(e) => match e.downcast_ref::<FlowyError>() {
+                Some(FlowyError::DirectoryNotFound(_)) | Some(FlowyError::NoWallpapers(_)) => (),
+                _ => return Err(e),
+            },
+        }
+    }
+
+    println!("No wallpapers found in:");
+    for dir in &candidates {
+        println!("  {}", dir.display());
+    }
+    println!("Next steps:");
+    if let Some(dir) = candidates.first() {
+        println!("  Put your wallpapers in {} and run flowy init again, or", dir.display());
+    }
+    println!("  Run flowy --dir <DIR> with the folder your wallpapers are in, or");
+    println!("  Run flowy --preset <NAME> to download a set of wallpapers");
+    Ok(())
+}
+
+/// Generates the config file from a list of wallpapers,
+/// spreading them evenly over the day
+pub fn generate_config_from_walls(walls: Vec<String>) -> Result<(), Box<dyn Error>> {
+    info!("<---- Normal Mode ---->");
+    write_config(&even_schedule(walls)?)?;
+    Ok(())
+}
+
+/// How a generated schedule splits the day between the wallpapers
+#[derive(Debug, Default, Clone)]
+pub struct SplitOptions {
+    /// Share of the time for each wallpaper. Even when `None`.
+    pub weights: Option<Vec<u32>>,
+    /// Part of the day the wallpapers are spread over, which may span
+    /// midnight. The whole day when `None`.
+    pub window: Option<(NaiveTime, NaiveTime)>,
+    /// Wallpaper shown outside `window`. The last wallpaper stays when `None`.
+    pub outside: Option<String>,
+}
+
+/// Generates the config file from a list of wallpapers, splitting
+/// the day between them as `split` says
+pub fn generate_config_split(walls: Vec<String>, split: &SplitOptions) -> Result<(), Box<dyn Error>> {
+    info!("<---- Split Mode ---->");
+    write_config(&split_schedule(walls, split)?)?;
+    Ok(())
+}
+
+/// Parses a single window of the day such as `08:00-22:00`
+pub fn parse_window(window: &str) -> Result<(NaiveTime, NaiveTime), Box<dyn Error>> {
+    match parse_active_hours(window)?[..] {
+        [window] => Ok(window),
+        _ => Err(format!("Expected a single HH:MM-HH:MM window, found {}", window).into()),
+    }
+}
+
+/// Returns how a wallpaper file is written in the config, an absolute path
+pub fn wall_from_path(path: &Path) -> Result<String, Box<dyn Error>> {
+    Ok(path.canonicalize()?.display().to_string())
+}
+
+/// Parses a list of weights such as `1,1,6,2`
+pub fn parse_weights(list: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    let weights = list
+        .split(',')
+        .map(|w| w.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if weights.contains(&0) {
+        return Err("Weights must be at least 1".into());
+    }
+    Ok(weights)
+}
+
+/// Spreads wallpapers evenly over the day
+fn even_schedule(walls: Vec<String>) -> Result<Config, Box<dyn Error>> {
+    split_schedule(walls, &SplitOptions::default())
+}
+
+/// Spreads wallpapers over the day, or over the window of `split`, each
+/// showing for a share of the time proportional to its weight.
+/// A window which ends before it starts wraps around midnight.
+fn split_schedule(mut walls: Vec<String>, split: &SplitOptions) -> Result<Config, Box<dyn Error>> {
+    if walls.is_empty() {
+        return Err("No wallpapers to schedule".into());
+    }
+    let even = vec![1; walls.len()];
+    let weights = split.weights.as_deref().unwrap_or(&even);
+    if weights.len() != walls.len() {
+        return Err(format!("Found {} wallpapers but {} weights", walls.len(), weights.len()).into());
+    }
+    if walls.len() > MAX_WALLPAPERS {
+        return Err(format!(
+            "Found {} wallpapers but at most {} (one per minute) can be scheduled",
+            walls.len(),
+            MAX_WALLPAPERS
+        )
+        .into());
+    }
+    // Start and length in seconds of the time shared by the wallpapers
+    let (start, length) = match split.window {
+        Some((start, end)) => {
+            let (start, end) = (start.num_seconds_from_midnight() as u64, end.num_seconds_from_midnight() as u64);
+            match (end + 86400 - start) % 86400 {
+                0 => (start, 86400),
+                length => (start, length),
+            }
+        }
+        None => (0, 86400),
+    };
     // Offset in seconds for each wallpaper
-    let div = 86400 / walls.len();
-    let mut times = Vec::new();
+    let total: u64 = weights.iter().map(|&w| w as u64).sum();
+    let mut times: Vec<String> = Vec::new();
+    let mut elapsed = 0;
+    for (i, &weight) in weights.iter().enumerate() {
+        let offset = (start + length * elapsed / total) % 86400;
+        let time = format!("{:02}:{:02}", offset / 3600, (offset / 60) % 60);
+        if times.last() == Some(&time) {
+            return Err(format!("The wallpaper {} would be shown for less than a minute", walls[i - 1]).into());
+        }
+        times.push(time);
+        elapsed += weight as u64;
+    }
+    // The outside wallpaper takes over when the window ends
+    if let (Some(outside), true) = (&split.outside, length < 86400) {
+        let end = (start + length) % 86400;
+        times.push(format!("{:02}:{:02}", end / 3600, (end / 60) % 60));
+        walls.push(outside.clone());
+    }
 
-    for i in 0..walls.len() {
-        let offset = div * i;
-        times.push(format!("{:02}:{:02}", offset / 3600, (offset / 60) % 60));
+    Ok(Config {
+        times,
+        walls,
+        ..Default::default()
+    })
+}
+
+/// Returns the schedule for `date` from a holiday file, if it names a
+/// wallpaper directory for that date (`YYYY-MM-DD`) or for that day of
+/// every year (`MM-DD`). A missing holiday file has no holidays.
+///
+/// ```toml
+/// "12-25" = "/home/me/Pictures/christmas"
+/// "2026-03-14" = "/home/me/Pictures/birthday"
+/// ```
+pub fn holiday_config(path: &Path, date: NaiveDate) -> Result<Option<Config>, Box<dyn Error>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let holidays: BTreeMap<String, PathBuf> = toml::from_str(&contents)?;
+    // A full date wins over a yearly one
+    let dir = holidays
+        .get(&date.format("%Y-%m-%d").to_string())
+        .or_else(|| holidays.get(&date.format("%m-%d").to_string()));
+    match dir {
+        Some(dir) => {
+            check_dir(dir)?;
+            let walls = get_dir(dir, "")?;
+            if walls.is_empty() {
+                return Err(FlowyError::NoWallpapers(dir.to_path_buf()).into());
+            }
+            Ok(Some(even_schedule(walls)?))
+        }
+        None => Ok(None),
     }
+}
 
-    let config = Config { times, walls };
+/// Writes a file by writing a temporary file next to it and renaming it
+/// into place, so that a crash never leaves the file half written
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", path.display()))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
 
-    let toml_string = toml::to_string(&config)?;
-    std::fs::write(&get_config_path()?, toml_string)?;
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
     Ok(())
 }
 
@@ -149,61 +1211,1193 @@ pub fn get_config_dir() -> Result<PathBuf, Box<dyn Error>> {
     Ok(config_file)
 }
 
+/// Format new config files are written in, if not the one of an existing config
+static CONFIG_FORMAT: Mutex<Option<ConfigFormat>> = Mutex::new(None);
+
+/// Makes flowy read and write `config.<format>`, e.g. `config.yaml`,
+/// instead of the config file it finds
+pub fn set_config_format(format: ConfigFormat) {
+    *CONFIG_FORMAT.lock().unwrap() = Some(format);
+}
+
+/// Config file used instead of the one in the config directory
+static CONFIG_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Makes flowy read and write the config at `path`, e.g. to keep
+/// separate schedules for work and home. Its extension gives the format.
+pub fn set_config_path(path: &Path) {
+    *CONFIG_PATH.lock().unwrap() = Some(path.to_path_buf());
+}
+
 /// Returns the path where the config file is stored
 fn get_config_path() -> Result<PathBuf, Box<dyn Error>> {
-    let mut config_file = get_config_dir()?;
-    config_file.push("config.toml");
-    Ok(config_file)
+    config_path_or_default(None)
+}
+
+/// Returns `path` if given, then the path set with `set_config_path`,
+/// and otherwise the first of `config.toml`, `config.json`, `config.yaml`
+/// and `config.yml` in the config directory which exists, or `config.toml` for a new config.
+fn config_path_or_default(path: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(path) = path {
+        return Ok(path.to_path_buf());
+    }
+    if let Some(path) = CONFIG_PATH.lock().unwrap().clone() {
+        return Ok(path);
+    }
+    let config_dir = get_config_dir()?;
+    if let Some(format) = *CONFIG_FORMAT.lock().unwrap() {
+        return Ok(config_dir.join(format!("config.{}", format.extension())));
+    }
+    let existing = format::CONFIG_FILES
+        .iter()
+        .map(|name| config_dir.join(name))
+        .find(|path| path.exists());
+    Ok(existing.unwrap_or_else(|| config_dir.join(format::CONFIG_FILES[0])))
+}
+
+/// The last wallpaper set by the daemon, kept so that a restart shows the
+/// same image again, even if the config was generated in another order since
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    /// Slot of the schedule, and its time
+    index: usize,
+    time: String,
+    /// Wallpaper shown in the slot
+    wall: String,
+    config_hash: String,
+}
+
+/// Returns the path where the daemon state is stored
+fn get_state_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut state_file = get_config_dir()?;
+    state_file.push("state.toml");
+    Ok(state_file)
+}
+
+/// Identifies a schedule so that state saved for another config is not reused.
+/// The order of the wallpapers is left out, so that the same wallpapers
+/// shuffled again count as the same schedule. SHA-256 is used as it is the
+/// same with every version of Rust, unlike the standard library's hasher.
+fn schedule_hash(times: &[String], walls: &[String]) -> String {
+    let mut sorted: Vec<&String> = walls.iter().collect();
+    sorted.sort();
+    let mut hasher = Sha256::new();
+    for time in times {
+        hasher.update(time.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update([1]);
+    for wall in sorted {
+        hasher.update(wall.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Returns the saved state, if any
+fn load_state() -> Option<State> {
+    toml::from_str(&std::fs::read_to_string(get_state_path().ok()?).ok()?).ok()
+}
+
+/// Saves the wallpaper that was just set in the slot at `index`
+fn save_state(index: usize, time: &str, wall: &str, config_hash: &str) -> Result<(), Box<dyn Error>> {
+    let toml_string = toml::to_string(&State {
+        index,
+        time: time.to_string(),
+        wall: wall.to_string(),
+        config_hash: config_hash.to_string(),
+    })?;
+    write_atomic(&get_state_path()?, &toml_string)?;
+    Ok(())
+}
+
+/// Returns the index of the wallpaper a restart should show in the slot at `index`:
+/// the one saved for that slot if the state was saved for this schedule,
+/// or else the scheduled one
+fn resumed_index(state: Option<&State>, times: &[String], walls: &[String], index: usize) -> usize {
+    state
+        .filter(|state| state.config_hash == schedule_hash(times, walls))
+        .filter(|state| state.index == index && times.get(index) == Some(&state.time))
+        .and_then(|state| walls.iter().position(|wall| *wall == state.wall))
+        .unwrap_or(index)
+}
+
+/// Returns the path of the file whose presence pauses the daemon
+fn get_pause_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut pause_file = get_config_dir()?;
+    pause_file.push("paused");
+    Ok(pause_file)
+}
+
+/// Pauses a running daemon. It keeps tracking the schedule
+/// but doesn't change the wallpaper until `resume` is called.
+pub fn pause() -> Result<(), Box<dyn Error>> {
+    std::fs::write(&get_pause_path()?, "")?;
+    Ok(())
+}
+
+/// Resumes a paused daemon, which then sets the wallpaper for the current time
+pub fn resume() -> Result<(), Box<dyn Error>> {
+    let pause_path = get_pause_path()?;
+    if pause_path.exists() {
+        std::fs::remove_file(&pause_path)?;
+    }
+    Ok(())
+}
+
+/// Returns whether the daemon has been paused
+pub fn is_paused() -> bool {
+    get_pause_path().map(|p| p.exists()).unwrap_or(false)
+}
+
+/// Settings of the daemon which are not part of the config file
+#[derive(Debug, Default)]
+pub struct DaemonOptions {
+    /// Scaling of the wallpaper, overriding the config's `mode`
+    pub mode: Option<WallpaperMode>,
+    /// Fade into each wallpaper, overriding the config's `transition`
+    pub transition: Option<Duration>,
+    /// Scaling of the wallpaper for individual monitors, counting from 0
+    pub fit_per_monitor: BTreeMap<usize, WallpaperMode>,
+    /// Blends each wallpaper into the next one in this many steps
+    pub morph_steps: Option<u32>,
+    /// Sets the wallpaper again when monitors are connected or removed, on X11
+    pub monitor_hotplug: bool,
+    /// Recomputes the solar schedule while running
+    pub solar_update: Option<SolarUpdate>,
+    /// Windows of the day in which the wallpaper may change. Always when empty.
+    pub active_hours: Vec<(NaiveTime, NaiveTime)>,
+    /// File naming wallpaper directories for special dates, see `holiday_config`
+    pub holiday_file: Option<PathBuf>,
+    /// Longest time between checks of the schedule, instead of `MAX_SLEEP`
+    pub interval: Option<Duration>,
+    /// Switches macOS to the light appearance at sunrise and dark at sunset
+    pub appearance: bool,
+}
+
+/// Parses windows of the day such as `08:00-12:00,13:00-23:00`.
+/// A window may span midnight, e.g. `22:00-02:00`.
+pub fn parse_active_hours(list: &str) -> Result<Vec<(NaiveTime, NaiveTime)>, Box<dyn Error>> {
+    let mut windows = Vec::new();
+    for window in list.split(',') {
+        let (start, end) = window
+            .split_once('-')
+            .ok_or_else(|| format!("Expected HH:MM-HH:MM, found {}", window))?;
+        windows.push((
+            NaiveTime::parse_from_str(start.trim(), "%H:%M")?,
+            NaiveTime::parse_from_str(end.trim(), "%H:%M")?,
+        ));
+    }
+    Ok(windows)
+}
+
+/// Returns whether `now` falls in one of the windows, or if there are none
+fn in_active_hours(windows: &[(NaiveTime, NaiveTime)], now: NaiveTime) -> bool {
+    windows.is_empty()
+        || windows.iter().any(|&(start, end)| {
+            if start <= end {
+                start <= now && now < end
+            } else {
+                // The window spans midnight
+                now >= start || now < end
+            }
+        })
+}
+
+/// How the daemon keeps a solar schedule up to date as sunrise and sunset move
+#[derive(Debug, Clone)]
+pub struct SolarUpdate {
+    /// Solar, elevation or phases
+    pub kind: ScheduleKind,
+    /// Wallpaper directory and location the schedule is generated from
+    pub dir: PathBuf,
+    pub lat: f64,
+    pub long: f64,
+    /// Solar events of a phases schedule, empty otherwise
+    pub phases: Vec<String>,
+    /// Tags of day and night wallpapers of a solar schedule
+    pub day_tag: String,
+    pub night_tag: String,
+    /// Time between recomputations, or once per local day at midnight if `None`
+    pub interval: Option<Duration>,
+}
+
+impl SolarUpdate {
+    /// Computes the schedule for today again, without writing the config
+    fn schedule(&self) -> Result<Config, Box<dyn Error>> {
+        match self.kind {
+            ScheduleKind::Elevation => elevation_config(&self.dir, self.lat, self.long),
+            ScheduleKind::Phases => phases_config(&self.dir, self.lat, self.long, &self.phases),
+            _ => solar_dir_config(&self.dir, self.lat, self.long, &self.day_tag, &self.night_tag),
+        }
+    }
+}
+
+/// Shortest allowed time between solar recomputations
+pub const MIN_SOLAR_UPDATE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Parses a duration such as `90`, `30s`, `15m`, `6h` or `1d`. Plain numbers are seconds.
+pub fn parse_duration(duration: &str) -> Result<Duration, Box<dyn Error>> {
+    let duration = duration.trim();
+    let (number, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => duration.split_at(i),
+        None => (duration, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit in {}, expected s, m, h or d", duration).into()),
+    };
+    Ok(Duration::from_secs(number.parse::<u64>()? * multiplier))
+}
+
+/// Turns a transition in seconds into a duration. 0 switches at once.
+pub fn parse_transition(seconds: f64) -> Result<Duration, Box<dyn Error>> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| format!("The transition must be a number of seconds, found {}", seconds).into())
 }
 
-/// Parses the config file and runs the daemon
-pub fn set_times(config: Config) -> Result<(), Box<dyn Error>> {
-    let walls = config.walls;
-    let times = config.times;
-    println!("Wallpapers:");
+/// Parses a list of per monitor scaling modes such as `0=fill,1=fit`
+pub fn parse_fit_per_monitor(list: &str) -> Result<BTreeMap<usize, WallpaperMode>, Box<dyn Error>> {
+    let mut fits = BTreeMap::new();
+    for entry in list.split(',') {
+        let (monitor, mode) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Expected MONITOR=MODE, found {}", entry))?;
+        fits.insert(monitor.trim().parse()?, mode.trim().parse()?);
+    }
+    Ok(fits)
+}
+
+/// Set when the daemon was asked to stop by SIGINT or SIGTERM
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Sets `SHUTDOWN` and sends to `wake` on SIGINT or SIGTERM
+fn handle_shutdown(wake: mpsc::Sender<()>) -> Result<(), Box<dyn Error>> {
+    ctrlc::set_handler(move || {
+        SHUTDOWN.store(true, Ordering::SeqCst);
+        let _ = wake.send(());
+    })?;
+    Ok(())
+}
+
+/// Parses the config file and runs the daemon until it is asked to stop
+pub fn set_times(config: Config, options: &DaemonOptions) -> Result<(), Box<dyn Error>> {
+    // A solar config remembers where it came from, so it is kept up to date
+    // without passing --solar again
+    let solar_update = match (&options.solar_update, config.schedule, &config.solar_dir) {
+        (Some(update), ..) => Some(update.clone()),
+        (None, Some(kind @ (ScheduleKind::Solar | ScheduleKind::Elevation | ScheduleKind::Phases)), Some(dir)) => {
+            match (config.lat, config.long) {
+                (Some(lat), Some(long)) => Some(SolarUpdate {
+                    kind,
+                    dir: dir.into(),
+                    lat,
+                    long,
+                    phases: config.phases.clone().unwrap_or_default(),
+                    day_tag: config.day_tag.clone().unwrap_or_else(|| DAY_TAG.to_string()),
+                    night_tag: config.night_tag.clone().unwrap_or_else(|| NIGHT_TAG.to_string()),
+                    interval: None,
+                }),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    // The appearance follows the sun of the solar schedule. The timetable
+    // is kept so that it is only computed again on a new day.
+    let mut appearance_sun = match (options.appearance, &solar_update) {
+        (false, _) => None,
+        (true, Some(update)) => Some(solar::Timetable::new(
+            Utc::now().timestamp() as f64,
+            update.lat,
+            update.long,
+        )),
+        (true, None) => return Err("--appearance needs a solar, elevation or phases schedule".into()),
+    };
+    // Whether the sun was up when the appearance was last looked at
+    let mut sun_was_up = None;
+    let mut style = Style::new(&config, options)?;
+    let mut walls = config.walls;
+    let mut times = config.times;
+    let mut dark_walls = config.dark_walls;
+    let per_monitor = config.per_monitor.unwrap_or_default();
+    let mut per_workspace = config.per_workspace.unwrap_or_default();
+    info!("Wallpapers:");
     for i in 0..times.len() {
-        println!("- {:?} = {:?}", times[i], &walls[i]);
+        info!("- {:?} = {:?}", times[i], &walls[i]);
+    }
+    let desktop_envt = DesktopEnvt::new()
+        .map_err(|e| format!("Could not determine the desktop environment: {}", e))?;
+    if let Some(tool) = desktop_envt.tool() {
+        info!("Using {} to set the wallpaper", tool);
+    }
+    // Monitors are only set one by one when they have their own scaling or wallpapers
+    let mut monitors = 0;
+    if !options.fit_per_monitor.is_empty() {
+        if !desktop_envt.capabilities().per_monitor {
+            warn!("Per monitor scaling is not supported here, ignoring it");
+        } else {
+            monitors = desktop_envt.monitor_count()?;
+            if let Some(monitor) = options.fit_per_monitor.keys().find(|&&m| m >= monitors) {
+                return Err(format!("No monitor {}, found {} monitors", monitor, monitors).into());
+            }
+        }
+    }
+    let mut monitor_walls = BTreeMap::new();
+    if !per_monitor.is_empty() {
+        if !desktop_envt.capabilities().per_monitor {
+            warn!("Per monitor wallpapers are not supported here, ignoring them");
+        } else {
+            monitor_walls = resolve_monitors(&desktop_envt, &per_monitor)?;
+            monitors = desktop_envt.monitor_count()?;
+        }
+    }
+    if !per_workspace.is_empty() && !desktop_envt.capabilities().per_workspace {
+        warn!("Per workspace wallpapers are not supported here, ignoring them");
+        per_workspace.clear();
     }
-    // Will throw an error if Desktop Envt is not supported
-    let desktop_envt = DesktopEnvt::new().expect("Desktop envt could not be determined");
-    // Create an instance of last_index pointing to None
+    // Woken up early when the config or the pause file changes, or to shut down
+    let (wake_sender, wake) = mpsc::channel();
+    // Stopping cleanly on Ctrl+C, or when systemd stops the service
+    handle_shutdown(wake_sender.clone())?;
+    let _watcher = watch_config_dir(wake_sender)
+        .map_err(|e| warn!("Can't watch the config, changes are noticed within minutes: {}", e))
+        .ok();
+    let mut config_modified = get_config_modified();
+    // Create an instance of last_index pointing to None, so that the
+    // wallpaper is always set at startup. Tools such as swaybg lose it on logout.
     let mut last_index = None;
-    println!("<--- Daemon Listening --->");
-    // This daemon checks every minute if the index of the wallpaper has changed
-    // If yes, then the new wallpaper is 
+    // The image shown before a restart is shown again while its slot lasts,
+    // even if the config was generated in another order since, e.g. shuffled
+    let mut config_hash = schedule_hash(&times, &walls);
+    let mut resumed = load_state();
+    let mut paused = false;
+    // Monitors seen on the last check, for noticing hotplugs
+    let mut monitor_hotplug = options.monitor_hotplug;
+    if monitor_hotplug && !desktop_envt.capabilities().hotplug {
+        warn!("Monitor changes can't be observed here, ignoring --monitor-hotplug");
+        monitor_hotplug = false;
+    }
+    let mut last_layout = None;
+    // Step of the blend into the next wallpaper when morphing
+    let mut last_step = 0;
+    // Wallpapers which could not be set since the daemon started
+    let mut failures = 0;
+    info!("<--- Daemon Listening --->");
+    // This daemon sleeps until the next wallpaper is due and checks
+    // if the index of the wallpaper has changed. If yes, the new wallpaper is set
+    // When the solar schedule was last computed
+    // Not yet, as the times in the config file are those of the day it was generated
+    let mut solar_updated: Option<DateTime<Local>> = None;
+    // The schedule replacing the config on a holiday, looked up once a day
+    let mut holiday: Option<Config> = None;
+    let mut holiday_checked = None;
+    // Whether systemd has been told that the daemon is up
+    let mut ready = false;
     loop {
+        if SHUTDOWN.load(Ordering::SeqCst) {
+            info!("Shutting down");
+            service::stopping();
+            return Ok(());
+        }
+        service::watchdog();
+        // Everything in this round is decided for the same moment, read from
+        // the wall clock rather than derived from how long the daemon slept
+        let now = Local::now();
+        // Reloading the config after it was edited
+        let modified = get_config_modified();
+        if modified != config_modified {
+            config_modified = modified;
+            match get_config() {
+                Ok(config) => {
+                    style = Style::new(&config, options).unwrap_or(style);
+                    times = config.times;
+                    walls = config.walls;
+                    dark_walls = config.dark_walls;
+                    if desktop_envt.capabilities().per_workspace {
+                        per_workspace = config.per_workspace.unwrap_or_default();
+                    }
+                    if desktop_envt.capabilities().per_monitor {
+                        let per_monitor = config.per_monitor.unwrap_or_default();
+                        match resolve_monitors(&desktop_envt, &per_monitor) {
+                            Ok(walls) => monitor_walls = walls,
+                            Err(e) => warn!("Keeping the old monitor wallpapers: {}", e),
+                        }
+                        if !monitor_walls.is_empty() && monitors == 0 {
+                            match desktop_envt.monitor_count() {
+                                Ok(count) => monitors = count,
+                                Err(e) => warn!("Could not count the monitors: {}", e),
+                            }
+                        }
+                    }
+                    config_hash = schedule_hash(&times, &walls);
+                    last_index = None;
+                    resumed = None;
+                    // The times in the file are those of the day it was written
+                    if solar_update.is_some() {
+                        solar_updated = None;
+                    }
+                    info!("Config reloaded");
+                }
+                Err(e) => warn!("Keeping the old schedule: {}", e),
+            }
+        }
+        // Recomputing sunrise and sunset
+        if let Some(update) = &solar_update {
+            let due = match (solar_updated, update.interval) {
+                (None, _) => true,
+                (Some(updated), Some(interval)) => (now - updated).to_std().unwrap_or_default() >= interval,
+                (Some(updated), None) => now.naive_local().date() != updated.naive_local().date(),
+            };
+            if due {
+                solar_updated = Some(now);
+                // Kept in memory, so that a hand edited config is not overwritten.
+                // A failure, e.g. an unmounted wallpaper folder or a phase the sun
+                // doesn't reach near the poles, keeps the old schedule until the next update.
+                match update.schedule() {
+                    Ok(config) => {
+                        times = config.times;
+                        walls = config.walls;
+                        dark_walls = config.dark_walls;
+                        config_hash = schedule_hash(&times, &walls);
+                        info!("Solar schedule updated");
+                    }
+                    Err(e) => warn!("Keeping the old solar schedule: {}", e),
+                }
+            }
+        }
+        // Switching to and from a holiday's wallpapers
+        if let Some(holiday_file) = &options.holiday_file {
+            let today = now.naive_local().date();
+            if holiday_checked != Some(today) {
+                holiday_checked = Some(today);
+                let was_holiday = holiday.is_some();
+                holiday = holiday_config(holiday_file, today).unwrap_or_else(|e| {
+                    warn!("Could not read the holiday file: {}", e);
+                    None
+                });
+                if holiday.is_some() {
+                    info!("Holiday wallpapers");
+                    last_index = None;
+                } else if was_holiday {
+                    info!("Holiday over");
+                    last_index = None;
+                }
+            }
+        }
+        let (times, walls, config_hash) = match &holiday {
+            Some(holiday) => {
+                let hash = schedule_hash(&holiday.times, &holiday.walls);
+                (&holiday.times, &holiday.walls, hash)
+            }
+            None => (&times, &walls, config_hash.clone()),
+        };
+        // Holding the current wallpaper while paused
+        if is_paused() != paused {
+            paused = !paused;
+            info!("{}", if paused { "Paused" } else { "Resumed" });
+            // Forces the wallpaper for the current time to be set on resume
+            last_index = None;
+        }
+        // Setting the wallpaper again on new monitors
+        if monitor_hotplug {
+            let layout = desktop_envt.monitor_layout().ok();
+            if last_layout.is_some() && layout != last_layout {
+                info!("Monitors changed");
+                last_index = None;
+            }
+            last_layout = layout;
+        }
         // Getting the current wallpaper's index
-        let current_index = get_current_wallpaper_idx(&times)?;
-        if Some(current_index) != last_index {
-            // Updating last_index to the current_index
-            last_index = Some(current_index);
+        let current_index = get_current_wallpaper_idx(times, now.time())?;
+        let step = match options.morph_steps {
+            Some(steps) => {
+                let progress = slot_progress(times, current_index, now.time())?;
+                (progress * steps as f64) as u32
+            }
+            None => 0,
+        };
+        // Outside the active hours the last wallpaper is kept
+        let active = in_active_hours(&options.active_hours, now.time());
+        if !paused && active && (Some(current_index) != last_index || step != last_step) {
+            // The saved image only stands in for the slot it was saved for
+            if resumed.as_ref().is_some_and(|state| state.index != current_index) {
+                resumed = None;
+            }
+            let shown = resumed_index(resumed.as_ref(), times, walls, current_index);
+            if shown != current_index {
+                info!("Resuming with the wallpaper shown before the restart");
+            }
             // Set current wallpaper
-            let wall = &walls[current_index];
-            println!("Set wallpaper: {:?} = {:?}", times[current_index], wall);
-            desktop_envt.set_wallpaper(wall)?;
+            let mut wall = walls[shown].clone();
+            if let (Some(steps), true) = (options.morph_steps, step > 0) {
+                let next = &walls[(current_index + 1) % walls.len()];
+                wall = images::blend(&wall, next, step as f64 / steps as f64).unwrap_or_else(|e| {
+                    warn!("Could not blend the wallpapers, using the current one: {}", e);
+                    wall
+                });
+            }
+            info!("Set wallpaper {}: {:?} = {:?}", current_index, times[current_index], wall);
+            // Workspaces don't have their own holiday wallpapers
+            let per_workspace = if holiday.is_some() { None } else { Some(&per_workspace) };
+            // Blended wallpapers have no dark or per monitor counterpart
+            let (dark, per_monitor) = match (&holiday, step) {
+                (None, 0) => (dark_walls.as_ref().and_then(|d| d.get(shown)), Some(&monitor_walls)),
+                _ => (None, None),
+            };
+            let monitor_walls: Vec<&str> = (0..monitors)
+                .map(|m| {
+                    per_monitor
+                        .and_then(|walls| walls.get(&m)?.get(shown))
+                        .map_or(&wall[..], |w| &w[..])
+                })
+                .collect();
+            let applied = apply_wallpaper(
+                &desktop_envt,
+                &wall,
+                dark.map(|d| &d[..]),
+                style,
+                &monitor_walls,
+                options,
+                per_workspace.into_iter().flatten().map(|(w, walls)| (w, walls.get(shown))),
+            );
+            // A wallpaper which can't be set, e.g. because it was deleted,
+            // is tried again after the next sleep rather than stopping the daemon
+            match applied {
+                Ok(()) => {
+                    // Updating last_index to the current_index
+                    last_index = Some(current_index);
+                    last_step = step;
+                    // The daemon keeps going without it, e.g. on a full disk
+                    if let Err(e) = save_state(current_index, &times[current_index], &walls[shown], &config_hash) {
+                        warn!("Could not save the state: {}", e);
+                    }
+                }
+                Err(e) => {
+                    failures += 1;
+                    error!("Could not set the wallpaper ({} failures so far): {}", failures, e);
+                }
+            }
+        }
+        if let (Some(tt), false) = (&mut appearance_sun, paused) {
+            follow_sun(&mut sun_was_up, tt);
         }
-        // Check every t seconds
-        // Change this if you would like a more accurate daemon
-        let t = 60;
-        thread::sleep(Duration::from_secs(t));
+        // The daemon counts as started once the first wallpaper was tried
+        if !ready {
+            service::ready();
+            ready = true;
+        }
+        // Sleeping until the next wallpaper, or anything else which may change it
+        let now = Local::now();
+        let mut changes = times
+            .iter()
+            .map(|t| NaiveTime::parse_from_str(t, "%H:%M"))
+            .collect::<Result<Vec<_>, _>>()?;
+        changes.extend(options.active_hours.iter().map(|&(start, _)| start));
+        if let Some(tt) = &mut appearance_sun {
+            tt.set_date(now.timestamp() as f64);
+            if let Some((sunrise, sunset)) = tt.get_sunrise_sunset() {
+                changes.push(solar::unix_to_local(sunrise).time());
+                changes.push(solar::unix_to_local(sunset).time());
+            }
+        }
+        let daily_solar = matches!(&solar_update, Some(update) if update.interval.is_none());
+        if daily_solar || options.holiday_file.is_some() {
+            changes.push(NaiveTime::from_hms(0, 0, 0));
+        }
+        let mut sleep = until_next(&changes, now.time()).min(options.interval.unwrap_or(MAX_SLEEP));
+        if let Some(interval) = solar_update.as_ref().and_then(|u| u.interval) {
+            let elapsed = solar_updated.map_or(Duration::ZERO, |updated| (now - updated).to_std().unwrap_or_default());
+            sleep = sleep.min(interval.saturating_sub(elapsed));
+        }
+        // Some changes can only be noticed by checking regularly
+        if paused || monitor_hotplug || options.morph_steps.is_some() {
+            sleep = sleep.min(options.interval.unwrap_or(POLL_INTERVAL));
+        }
+        if let Some(interval) = service::watchdog_interval() {
+            sleep = sleep.min(interval);
+        }
+        if let Ok(()) = wake.recv_timeout(sleep) {
+            // Changes often come in bursts, e.g. when an editor saves a file
+            thread::sleep(Duration::from_millis(100));
+            while wake.try_recv().is_ok() {}
+        }
+        // The sleep doesn't count time spent suspended, so a much later wall
+        // clock means the machine was asleep. Some desktops lose the wallpaper
+        // on resume, so it is set again even if it is still the right one.
+        let slept = (Local::now() - now).to_std().unwrap_or_default();
+        if slept > sleep + SUSPEND_THRESHOLD {
+            info!("Woke up after {} minutes", slept.as_secs() / 60);
+            last_index = None;
+        }
+    }
+}
+
+/// Returns when the config file was last modified
+fn get_config_modified() -> Option<SystemTime> {
+    let config_path = get_config_path().ok()?;
+    std::fs::metadata(config_path).and_then(|m| m.modified()).ok()
+}
+
+/// Watches the config folder, and the folder of a config given with
+/// --config, sending to `wake` when the config or the pause file changes.
+/// Watching stops when the watcher is dropped.
+fn watch_config_dir(wake: mpsc::Sender<()>) -> Result<notify::RecommendedWatcher, Box<dyn Error>> {
+    use notify::Watcher;
+    let mut watched: Vec<String> = format::CONFIG_FILES.iter().map(|name| name.to_string()).collect();
+    watched.push("paused".to_string());
+    let custom = CONFIG_PATH.lock().unwrap().clone();
+    if let Some(name) = custom.as_deref().and_then(Path::file_name) {
+        watched.push(name.to_string_lossy().into_owned());
+    }
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let relevant = event.is_ok_and(|event| {
+            event.paths.iter().any(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| watched.iter().any(|w| w == name))
+            })
+        });
+        if relevant {
+            let _ = wake.send(());
+        }
+    })?;
+    let config_dir = get_config_dir()?;
+    watcher.watch(&config_dir, notify::RecursiveMode::NonRecursive)?;
+    if let Some(dir) = custom.as_deref().and_then(Path::parent).filter(|d| *d != config_dir) {
+        // An empty parent is the working directory
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+    }
+    Ok(watcher)
+}
+
+/// Switches macOS to the light appearance when the sun rises and to the dark
+/// one when it sets. Only sunrise and sunset switch it, so an appearance
+/// picked by hand in between is kept until the next one.
+fn follow_sun(sun_was_up: &mut Option<bool>, tt: &mut solar::Timetable) {
+    let now = Utc::now().timestamp() as f64;
+    tt.set_date(now);
+    let sun_up = tt.sun_up_at(now);
+    if *sun_was_up == Some(sun_up) {
+        return;
+    }
+    *sun_was_up = Some(sun_up);
+    let name = if sun_up { "light" } else { "dark" };
+    match appearance::is_dark() {
+        Ok(dark) if dark != sun_up => debug!("The appearance is already {}", name),
+        _ => match appearance::set_dark(!sun_up) {
+            Ok(()) => info!("Switched to the {} appearance", name),
+            Err(e) => warn!("Could not switch to the {} appearance: {}", name, e),
+        },
+    }
+}
+
+/// How wallpapers are shown, from the config unless given on the command line
+#[derive(Debug, Default, Copy, Clone)]
+struct Style {
+    mode: Option<WallpaperMode>,
+    transition: Option<Duration>,
+}
+
+impl Style {
+    fn new(config: &Config, options: &DaemonOptions) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            mode: options.mode.or(config.wallpaper_mode()?),
+            transition: options.transition.or(config.wallpaper_transition()?),
+        })
+    }
+}
+
+/// Sets a wallpaper on the whole desktop, with its dark mode version if there
+/// is one, or `monitor_walls` on each monitor with its own scaling, and the
+/// wallpapers of individual workspaces. Monitors without their own scaling use
+/// the mode of `style`. Its transition is only used for a single wallpaper
+/// scaled to fill, the scaling desktops fade with.
+fn apply_wallpaper<'a>(
+    desktop_envt: &DesktopEnvt,
+    wall: &str,
+    dark: Option<&str>,
+    style: Style,
+    monitor_walls: &[&str],
+    options: &DaemonOptions,
+    workspaces: impl Iterator<Item = (&'a String, Option<&'a String>)>,
+) -> Result<(), Box<dyn Error>> {
+    let mode = style.mode;
+    let transition = style
+        .transition
+        .filter(|t| !t.is_zero() && desktop_envt.capabilities().transition);
+    if monitor_walls.is_empty() {
+        match (mode, dark, transition) {
+            (None | Some(WallpaperMode::Fill), None, Some(duration)) => {
+                desktop_envt.set_wallpaper_with_transition(wall, duration)?
+            }
+            (Some(mode), ..) => desktop_envt.set_wallpaper_with_mode(wall, mode)?,
+            (None, None, _) => desktop_envt.set_wallpaper(wall)?,
+            (None, Some(_), _) => {}
+        }
+        if let Some(dark) = dark {
+            desktop_envt.set_wallpaper_light_dark(wall, dark)?;
+        }
+    }
+    for (monitor, wall) in monitor_walls.iter().enumerate() {
+        let mode = options.fit_per_monitor.get(&monitor).copied().or(mode).unwrap_or_default();
+        desktop_envt.set_wallpaper_for_monitor(monitor, wall, mode)?;
+    }
+    for (workspace, wall) in workspaces {
+        match wall {
+            Some(wall) => desktop_envt.set_wallpaper_for_workspace(workspace, wall)?,
+            None => warn!("No wallpaper for workspace {}", workspace),
+        }
+    }
+    Ok(())
+}
+
+/// Turns the keys of a config's `per_monitor` table, monitor numbers or names,
+/// into monitor numbers
+fn resolve_monitors(
+    desktop_envt: &DesktopEnvt,
+    per_monitor: &BTreeMap<String, Vec<String>>,
+) -> Result<BTreeMap<usize, Vec<String>>, Box<dyn Error>> {
+    if per_monitor.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let names = desktop_envt.monitor_names()?;
+    let mut resolved = BTreeMap::new();
+    for (key, walls) in per_monitor {
+        let monitor = key
+            .parse::<usize>()
+            .ok()
+            .filter(|&m| m < names.len())
+            .or_else(|| names.iter().position(|name| name == key))
+            .ok_or_else(|| format!("No monitor {}, found {}", key, names.join(", ")))?;
+        resolved.insert(monitor, walls.clone());
+    }
+    Ok(resolved)
+}
+
+/// Longest the daemon sleeps at once, so that it notices a changed
+/// clock, e.g. after a suspend, in reasonable time
+const MAX_SLEEP: Duration = Duration::from_secs(5 * 60);
+/// How much longer than planned a sleep must take to count as a suspend
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(60);
+/// How often the daemon checks for changes it isn't told about, such as
+/// monitors being connected
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Returns the time from `now` until the first of `times` after it,
+/// wrapping past midnight. A time equal to `now` is a day away.
+fn until_next(times: &[NaiveTime], now: NaiveTime) -> Duration {
+    let now = now.num_seconds_from_midnight() as i64;
+    times
+        .iter()
+        .map(|time| match (time.num_seconds_from_midnight() as i64 - now).rem_euclid(86400) {
+            0 => 86400,
+            seconds => seconds,
+        })
+        .min()
+        .map_or(Duration::from_secs(86400), |seconds| Duration::from_secs(seconds as u64))
+}
+
+/// Returns how far `now` is through the slot of the wallpaper at `index`,
+/// from 0 at its start to 1 at the start of the next wallpaper
+fn slot_progress(wall_times: &[String], index: usize, now: NaiveTime) -> Result<f64, Box<dyn Error>> {
+    let seconds = |time: NaiveTime| time.num_seconds_from_midnight() as i64;
+    let start = seconds(NaiveTime::parse_from_str(&wall_times[index], "%H:%M")?);
+    let next = &wall_times[(index + 1) % wall_times.len()];
+    let end = seconds(NaiveTime::parse_from_str(next, "%H:%M")?);
+
+    // Slots can wrap past midnight, and a single wallpaper lasts all day
+    let mut length = (end - start).rem_euclid(86400);
+    if length == 0 {
+        length = 86400;
     }
+    let elapsed = (seconds(now) - start).rem_euclid(86400);
+
+    Ok(elapsed as f64 / length as f64)
 }
 
-/// Returns the index of the wallpaper which should be displayed now.
+/// Prints the wallpaper which should be displayed now.
+///
+/// With `explain`, it also prints each step of the decision: the current time,
+/// the times surrounding it and the resulting index.
+pub fn print_current(config: &Config, explain: bool) -> Result<(), Box<dyn Error>> {
+    let now = Local::now().time();
+    let index = get_current_wallpaper_idx(&config.times, now)?;
+    let next = (index + 1) % config.times.len();
+
+    if explain {
+        println!("Local time: {}", now.format("%H:%M:%S"));
+        println!("Schedule has {} entries", config.times.len());
+        println!(
+            "Latest time not after now: {:?} (index {})",
+            config.times[index], index
+        );
+        println!("Next time: {:?} (index {})", config.times[next], next);
+        if index == config.times.len() - 1 {
+            println!("The last entry lasts until the first one, past midnight");
+        }
+        if let (Some(lat), Some(long)) = (config.lat, config.long) {
+            explain_solar_events(config, lat, long);
+        }
+    }
+    println!("{}", config.walls[index]);
+
+    Ok(())
+}
+
+/// Prints today's sunrise and sunset, and the events of a phases schedule,
+/// with which side of each the current time is on
+fn explain_solar_events(config: &Config, lat: f64, long: f64) {
+    let now = Utc::now().timestamp();
+    let tt = solar::Timetable::new(now as f64, lat, long);
+    let side = |time: i64| if now < time { "ahead" } else { "passed" };
+    let local = |time: i64| solar::unix_to_local(time).format("%H:%M").to_string();
+    println!("Location: Lat: {} Long: {}", lat, long);
+    match tt.get_sunrise_sunset() {
+        Some((sunrise, sunset)) => {
+            println!("Sunrise: {} ({})", local(sunrise), side(sunrise));
+            println!("Sunset: {} ({})", local(sunset), side(sunset));
+        }
+        None if tt.sun_up_at_noon() => println!("The sun does not set today"),
+        None => println!("The sun does not rise today"),
+    }
+    println!("The sun is {}", if tt.sun_up_at(now as f64) { "up" } else { "down" });
+    for phase in config.phases.iter().flatten() {
+        let time = solar::SolarTime::from_name(phase).and_then(|event| tt.get(&event).copied());
+        match time {
+            Some(time) => println!("Phase {}: {} ({})", phase, local(time.round() as i64), side(time.round() as i64)),
+            None => println!("Phase {}: not reached today", phase),
+        }
+    }
+}
+
+/// Environment variables which decide the desktop flowy detects and how it sets the wallpaper
+const ENVIRONMENT_VARS: &[&str] = &[
+    "XDG_CURRENT_DESKTOP",
+    "DESKTOP_SESSION",
+    "GDMSESSION",
+    "XDG_SESSION_TYPE",
+    "WAYLAND_DISPLAY",
+    "DISPLAY",
+    "FLOWY_BACKEND",
+    "FLOWY_TOOL",
+];
+
+/// Prints what flowy detects about the desktop, for bug reports: the desktop,
+/// the variables it was detected from, the config path and the current wallpaper.
+/// Nothing is changed.
+pub fn print_environment() -> Result<(), Box<dyn Error>> {
+    let desktop_envt = DesktopEnvt::new();
+    match &desktop_envt {
+        Ok(desktop_envt) => {
+            println!("Desktop: {:?}", desktop_envt);
+            if let Some(tool) = desktop_envt.tool() {
+                println!("Tool: {}", tool);
+            }
+            println!("Capabilities: {:?}", desktop_envt.capabilities());
+        }
+        Err(e) => println!("Desktop: not detected ({})", e),
+    }
+    for var in ENVIRONMENT_VARS {
+        match std::env::var(var) {
+            Ok(value) => println!("{}={:?}", var, value),
+            Err(_) => println!("{} is not set", var),
+        }
+    }
+
+    let config_path = get_config_path()?;
+    let missing = if config_path.exists() { "" } else { " (missing)" };
+    println!("Config: {}{}", config_path.display(), missing);
+
+    if let Ok(desktop_envt) = desktop_envt {
+        match desktop_envt.get_wallpaper() {
+            Ok(wall) => println!("Wallpaper: {}", wall.display()),
+            Err(e) => println!("Wallpaper: could not be read ({})", e),
+        }
+    }
+    Ok(())
+}
+
+/// Prints the wallpaper the schedule shows now and the one it changes to next,
+/// with the times of both, without starting the daemon
+pub fn print_status(config: &Config) -> Result<(), Box<dyn Error>> {
+    config.validate()?;
+    let now = Local::now().time();
+    let index = get_current_wallpaper_idx(&config.times, now)?;
+    let next = (index + 1) % config.times.len();
+    let next_time = NaiveTime::parse_from_str(&config.times[next], "%H:%M")?;
+    let wait = until_next(&[next_time], now).as_secs();
+
+    let shows_for = human_duration(config.durations()?[index]);
+    println!(
+        "Current: {} (since {}, shows for {})",
+        config.walls[index], config.times[index], shows_for
+    );
+    // The schedule wraps around midnight
+    let tomorrow = if next_time <= now { " tomorrow" } else { "" };
+    println!(
+        "Next: {} at {}{} (in {}h {:02}m)",
+        config.walls[next],
+        config.times[next],
+        tomorrow,
+        wait / 3600,
+        wait % 3600 / 60
+    );
+    if is_paused() {
+        println!("The daemon is paused");
+    }
+    Ok(())
+}
+
+/// Prints the sun's current elevation in degrees and the part of the day
+/// at a location, on one line or as JSON
+pub fn print_sun_now(lat: f64, long: f64, json: bool) {
+    let now = DateTime::timestamp(&Utc::now()) as f64;
+    let elevation = solar::solar_elevation(now, lat, long);
+    // Rising if it will be higher in a minute
+    let rising = solar::solar_elevation(now + 60.0, lat, long) > elevation;
+    let phase = solar::sun_phase(elevation, rising);
+    if json {
+        let (next, at) = solar::Timetable::new(now, lat, long).next_event(now);
+        let at = solar::unix_to_local(at.round() as i64).format("%H:%M");
+        println!(
+            "{{\"elevation\": {:.2}, \"phase\": \"{}\", \"next\": \"{}\", \"next_at\": \"{}\"}}",
+            elevation,
+            phase,
+            next.name(),
+            at
+        );
+    } else {
+        println!("{:.2} {}", elevation, phase);
+    }
+}
+
+/// Sets the wallpaper the config schedules for now once, for running
+/// flowy from cron or a login hook instead of as a daemon
+pub fn apply(config: &Config) -> Result<(), Box<dyn Error>> {
+    config.validate()?;
+    let index = get_current_wallpaper_idx(&config.times, Local::now().time())?;
+    apply_index(config, index)
+}
+
+/// Sets the wallpaper at `index` of the config once
+fn apply_index(config: &Config, index: usize) -> Result<(), Box<dyn Error>> {
+    let desktop_envt = DesktopEnvt::new()?;
+    let per_workspace = match (&config.per_workspace, desktop_envt.capabilities().per_workspace) {
+        (Some(per_workspace), true) => Some(per_workspace),
+        _ => None,
+    };
+    let per_monitor = match (&config.per_monitor, desktop_envt.capabilities().per_monitor) {
+        (Some(per_monitor), true) => resolve_monitors(&desktop_envt, per_monitor)?,
+        _ => BTreeMap::new(),
+    };
+    let monitors = if per_monitor.is_empty() { 0 } else { desktop_envt.monitor_count()? };
+    let monitor_walls: Vec<&str> = (0..monitors)
+        .map(|m| per_monitor.get(&m).and_then(|w| w.get(index)).unwrap_or(&config.walls[index]))
+        .map(|w| &w[..])
+        .collect();
+    info!("Set wallpaper {}: {:?} = {:?}", index, config.times[index], config.walls[index]);
+    apply_wallpaper(
+        &desktop_envt,
+        &config.walls[index],
+        config.dark_walls.as_ref().and_then(|d| d.get(index)).map(|d| &d[..]),
+        Style::new(config, &DaemonOptions::default())?,
+        &monitor_walls,
+        &DaemonOptions::default(),
+        per_workspace.into_iter().flatten().map(|(w, walls)| (w, walls.get(index))),
+    )
+}
+
+/// Prints the schedule of a config with how long each wallpaper is shown,
+/// marking the wallpaper which is shown now
+pub fn print_schedule(config: &Config) -> Result<(), Box<dyn Error>> {
+    config.validate()?;
+    let current = get_current_wallpaper_idx(&config.times, Local::now().time())?;
+    let durations = config.durations()?;
+    for (i, (time, wall)) in config.times.iter().zip(config.walls.iter()).enumerate() {
+        let marker = if i == current { " <- now" } else { "" };
+        println!("{} = {} (shows for {}){}", time, wall, human_duration(durations[i]), marker);
+    }
+    Ok(())
+}
+
+/// Formats a duration of whole minutes such as `1h20m`, `45m` or `6h`
+fn human_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{:02}m", h, m),
+    }
+}
+
+/// Compares the wallpaper on the desktop with the one the config schedules
+/// for now, printing what was found. Fails if they differ.
+pub fn audit(config: &Config) -> Result<(), Box<dyn Error>> {
+    config.validate()?;
+    let now = Local::now().time();
+    let index = get_current_wallpaper_idx(&config.times, now)?;
+    let next = (index + 1) % config.times.len();
+    let expected = &config.walls[index];
+
+    let desktop_envt = DesktopEnvt::new()?;
+    match desktop_envt.tool() {
+        Some(tool) => println!("Backend: {:?} using {}", desktop_envt, tool),
+        None => println!("Backend: {:?}", desktop_envt),
+    }
+    println!("Expected: {} (since {})", expected, config.times[index]);
+    // Some desktops can't report their wallpaper, e.g. X11 with xwallpaper,
+    // which is no sign of a wrong wallpaper
+    let actual = match desktop_envt.get_wallpaper() {
+        Ok(actual) => actual.display().to_string(),
+        Err(e) => {
+            println!("Actual: unknown ({})", e);
+            println!("Next change: {} at {}", config.walls[next], config.times[next]);
+            println!("Can't tell whether the wallpaper matches the schedule");
+            return Ok(());
+        }
+    };
+    println!("Actual: {}", actual);
+    println!("Next change: {} at {}", config.walls[next], config.times[next]);
+
+    if normalize_wallpaper(&actual) != normalize_wallpaper(expected) {
+        return Err("The wallpaper on the desktop is not the scheduled one".into());
+    }
+    println!("The wallpaper matches the schedule");
+    Ok(())
+}
+
+/// Returns a wallpaper path without quotes, or decoded from a `file://` URI,
+/// so that paths reported by different desktops can be compared
+pub fn normalize_wallpaper(wall: &str) -> String {
+    let wall = wall.trim().trim_matches(|c| c == '"' || c == '\'');
+    wallpaper_rs::from_file_uri(wall)
+}
+
+/// Sets the wallpaper to the image at `path` once, without a config.
+/// The path, or a `file://` URI, is made absolute as it is in generated configs.
+pub fn set_wallpaper(path: &str) -> Result<(), Box<dyn Error>> {
+    let wall = wall_from_path(Path::new(&wallpaper_rs::from_file_uri(path)))?;
+    let desktop_envt = DesktopEnvt::new()
+        .map_err(|e| format!("Could not determine the desktop environment: {}", e))?;
+    info!("Set wallpaper: {:?}", wall);
+    desktop_envt.set_wallpaper(&wall)
+}
+
+/// Returns the scaling set in the config, if there is a config and it sets one
+fn config_mode() -> Option<WallpaperMode> {
+    get_config().ok()?.wallpaper_mode().ok()?
+}
+
+/// Sets a single wallpaper and keeps it set, setting it again whenever
+/// another program changes the wallpaper, with the scaling of the config.
+/// Editing the config sets it again. It runs until SIGINT or SIGTERM.
+pub fn set_and_watch(path: &Path) -> Result<(), Box<dyn Error>> {
+    let wall = wall_from_path(path)?;
+    let desktop_envt = DesktopEnvt::new()?;
+    let set = |mode: Option<WallpaperMode>| {
+        info!("Set wallpaper: {:?}", wall);
+        match mode {
+            Some(mode) => desktop_envt.set_wallpaper_with_mode(&wall, mode),
+            None => desktop_envt.set_wallpaper(&wall),
+        }
+    };
+    let mut mode = config_mode();
+    set(mode)?;
+
+    // Woken up early when the config changes, or to shut down
+    let (wake_sender, wake) = mpsc::channel();
+    handle_shutdown(wake_sender.clone())?;
+    let _watcher = watch_config_dir(wake_sender)
+        .map_err(|e| warn!("Can't watch the config, changes are noticed within minutes: {}", e))
+        .ok();
+    let mut config_modified = get_config_modified();
+    loop {
+        let _ = wake.recv_timeout(POLL_INTERVAL);
+        if SHUTDOWN.load(Ordering::SeqCst) {
+            info!("Shutting down");
+            return Ok(());
+        }
+        let mut changed = false;
+        let modified = get_config_modified();
+        if modified != config_modified {
+            config_modified = modified;
+            mode = config_mode();
+            info!("Config changed");
+            changed = true;
+        }
+        // Some desktops can't report their wallpaper, which counts as unchanged
+        match desktop_envt.get_wallpaper() {
+            Ok(current) if normalize_wallpaper(&current.display().to_string()) != normalize_wallpaper(&wall) => {
+                info!("Wallpaper changed to {:?}, setting it back", current);
+                changed = true;
+            }
+            Ok(_) => (),
+            Err(e) => debug!("Can't tell if the wallpaper changed: {}", e),
+        }
+        if changed {
+            if let Err(e) = set(mode) {
+                error!("Could not set the wallpaper: {}", e);
+            }
+        }
+    }
+}
+
+/// Measures how long the desktop takes to set a wallpaper.
+///
+/// The first wallpaper of the config is set `runs` times and the
+/// minimum, average and maximum durations are printed.
+/// The wallpaper shown before the benchmark is restored afterwards.
+pub fn benchmark(config: &Config, runs: usize) -> Result<(), Box<dyn Error>> {
+    let wall = config.walls.first().ok_or("No wallpapers in the config")?;
+    let desktop_envt = DesktopEnvt::new()?;
+    // Some desktops can't report their wallpaper, which is then left as the benchmark's
+    let original = desktop_envt
+        .get_wallpaper()
+        .map_err(|e| warn!("The wallpaper can't be read, so it won't be restored: {}", e))
+        .ok();
+
+    info!("Setting {:?} {} times", wall, runs);
+    let mut durations = Vec::with_capacity(runs);
+    let timed = (0..runs).try_for_each(|_| -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        desktop_envt.set_wallpaper(wall)?;
+        durations.push(start.elapsed());
+        Ok(())
+    });
+    // Restored even if a run failed
+    if let Some(original) = original {
+        if let Err(e) = desktop_envt.set_wallpaper(&original.display().to_string()) {
+            warn!("Could not restore the wallpaper {}: {}", original.display(), e);
+        }
+    }
+    timed?;
+
+    let min = durations.iter().min().copied().unwrap_or_default();
+    let max = durations.iter().max().copied().unwrap_or_default();
+    let avg = durations.iter().sum::<Duration>() / runs.max(1) as u32;
+    println!("min: {:?} avg: {:?} max: {:?}", min, avg, max);
+
+    Ok(())
+}
+
+/// Returns the index of the wallpaper which should be displayed at `curr_time`.
 ///
 /// For example, if the times are "00:00", "01:00" and "02:00", the first image
 /// should be shown from 00:00 to 00:59 and the second image from 01:00 to 01:59.
 ///
 /// Therefore, this function returns the index of the _last_ time that isn't
-/// greater than the current time.
-fn get_current_wallpaper_idx(wall_times: &[String]) -> Result<usize, Box<dyn Error>> {
+/// greater than the current time. Before the first time, the last
+/// wallpaper is still shown from the day before.
+fn get_current_wallpaper_idx(wall_times: &[String], curr_time: NaiveTime) -> Result<usize, Box<dyn Error>> {
     if wall_times.is_empty() {
-        panic!("Array of times can't be empty");
+        return Err("Array of times can't be empty".into());
     }
 
-    // Get the current time
-    let curr_time = Local::now().time();
-
     // Looping through times to compare all of them
     for i in 0..(wall_times.len() - 1) {
         let time = NaiveTime::parse_from_str(&wall_times[i], "%H:%M")?;
@@ -217,5 +2411,289 @@ fn get_current_wallpaper_idx(wall_times: &[String]) -> Result<usize, Box<dyn Err
         }
     }
 
-    return Ok(wall_times.len() - 1);
+    Ok(wall_times.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wallpaper names `0.png`, `1.png`, ... for schedules which don't read them
+    fn walls(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("{}.png", i)).collect()
+    }
+
+    #[test]
+    fn even_schedule_rejects_more_wallpapers_than_minutes() {
+        let err = even_schedule(walls(2000)).unwrap_err();
+        assert!(err.to_string().contains("at most 1440"), "{}", err);
+    }
+
+    #[test]
+    fn even_schedule_fits_one_wallpaper_per_minute() {
+        let config = even_schedule(walls(MAX_WALLPAPERS)).unwrap();
+        assert_eq!(config.times.len(), MAX_WALLPAPERS);
+        assert_eq!(config.times[1], "00:01");
+        assert_eq!(config.times.last().unwrap(), "23:59");
+    }
+
+    #[test]
+    fn resumed_index_shows_the_saved_wallpaper_of_a_reshuffled_schedule() {
+        let times = vec!["00:00".to_string(), "12:00".to_string()];
+        let saved = vec!["a.png".to_string(), "b.png".to_string()];
+        let state = State {
+            index: 0,
+            time: "00:00".to_string(),
+            wall: "a.png".to_string(),
+            config_hash: schedule_hash(&times, &saved),
+        };
+        // The same wallpapers shuffled into another order
+        let shuffled = vec!["b.png".to_string(), "a.png".to_string()];
+        assert_eq!(resumed_index(Some(&state), &times, &shuffled, 0), 1);
+        // Another slot shows its scheduled wallpaper
+        assert_eq!(resumed_index(Some(&state), &times, &shuffled, 1), 1);
+    }
+
+    #[test]
+    fn resumed_index_ignores_state_of_another_schedule() {
+        let times = vec!["00:00".to_string(), "12:00".to_string()];
+        let walls = vec!["a.png".to_string(), "b.png".to_string()];
+        let state = State {
+            index: 0,
+            time: "00:00".to_string(),
+            wall: "b.png".to_string(),
+            config_hash: schedule_hash(&["06:00".to_string(), "12:00".to_string()], &walls),
+        };
+        assert_eq!(resumed_index(Some(&state), &times, &walls, 0), 0);
+        assert_eq!(resumed_index(None, &times, &walls, 0), 0);
+    }
+
+    #[test]
+    fn write_atomic_keeps_the_old_file_when_a_write_is_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_atomic(&path, "times = []").unwrap();
+        // A crash while writing leaves a half written temporary file behind
+        let tmp_path = dir.path().join(".config.toml.tmp");
+        std::fs::write(&tmp_path, "tim").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "times = []");
+
+        // The next write replaces it
+        write_atomic(&path, "times = [\"00:00\"]").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "times = [\"00:00\"]");
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn parse_duration_reads_units() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(parse_duration(" 6h ").unwrap(), Duration::from_secs(6 * 3600));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+    }
+
+    #[test]
+    fn parse_duration_rejects_unknown_units() {
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn parse_active_hours_reads_several_windows() {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+        assert_eq!(
+            parse_active_hours("08:00-12:00, 13:30-23:00").unwrap(),
+            vec![(hm(8, 0), hm(12, 0)), (hm(13, 30), hm(23, 0))]
+        );
+        assert!(parse_active_hours("08:00").is_err());
+        assert!(parse_active_hours("08:00-25:00").is_err());
+    }
+
+    #[test]
+    fn in_active_hours_handles_windows_across_midnight() {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+        let night = parse_active_hours("22:00-02:00").unwrap();
+        assert!(in_active_hours(&night, hm(23, 0)));
+        assert!(in_active_hours(&night, hm(1, 59)));
+        assert!(!in_active_hours(&night, hm(2, 0)));
+        assert!(!in_active_hours(&night, hm(12, 0)));
+        // No windows means always
+        assert!(in_active_hours(&[], hm(12, 0)));
+    }
+
+    /// 2024-06-21 00:00 UTC
+    const MIDSUMMER: i64 = 1_718_928_000;
+
+    #[test]
+    fn elevation_schedule_brightens_towards_noon() {
+        let config = elevation_schedule(walls(3), 51.5, 0.0, MIDSUMMER).unwrap();
+        // The darkest wallpaper around midnight, the brightest around noon
+        assert_eq!(config.times[0], "00:00");
+        assert_eq!(config.walls[0], "0.png");
+        let noon = get_current_wallpaper_idx(&config.times, NaiveTime::from_hms(12, 0, 0)).unwrap();
+        assert_eq!(config.walls[noon], "2.png");
+        // Every band is entered on the way up and left on the way down
+        assert_eq!(config.walls, ["0.png", "1.png", "2.png", "1.png", "0.png"]);
+    }
+
+    #[test]
+    fn elevation_schedule_near_the_pole() {
+        // The elevation barely changes, but every time still gets a wallpaper
+        let config = elevation_schedule(walls(4), 89.9, 0.0, MIDSUMMER).unwrap();
+        assert_eq!(config.times.len(), config.walls.len());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn generate_fails_on_an_empty_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let err = Flowy::generate(Some(&config_path), dir.path()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<FlowyError>(), Some(FlowyError::NoWallpapers(_))));
+        assert!(!config_path.exists());
+    }
+
+    #[test]
+    fn get_dir_returns_only_images() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("day.PNG"), "").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+        std::fs::create_dir(dir.path().join("old.png")).unwrap();
+        let walls = get_dir(dir.path(), "").unwrap();
+        assert_eq!(walls, [dir.path().join("day.PNG").display().to_string()]);
+    }
+
+    #[test]
+    fn current_index_after_waking_from_suspend() {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+        let times: Vec<String> = vec!["06:00".into(), "12:00".into(), "18:00".into()];
+        // Suspended before noon and woken in the evening
+        assert_eq!(get_current_wallpaper_idx(&times, hm(11, 59)).unwrap(), 0);
+        assert_eq!(get_current_wallpaper_idx(&times, hm(19, 30)).unwrap(), 2);
+        // Suspended before midnight and woken in the night, in a schedule wrapping past it
+        let times: Vec<String> = vec!["22:00".into(), "02:00".into(), "09:00".into()];
+        assert_eq!(get_current_wallpaper_idx(&times, hm(23, 0)).unwrap(), 0);
+        assert_eq!(get_current_wallpaper_idx(&times, hm(3, 0)).unwrap(), 1);
+    }
+
+    #[test]
+    fn current_index_around_the_schedule() {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+        let times: Vec<String> = vec!["06:00".into(), "12:00".into(), "18:00".into()];
+        // Before the first time the last wallpaper of the day before stays
+        assert_eq!(get_current_wallpaper_idx(&times, hm(5, 0)).unwrap(), 2);
+        assert_eq!(get_current_wallpaper_idx(&times, hm(12, 0)).unwrap(), 1);
+        assert_eq!(get_current_wallpaper_idx(&times, hm(23, 59)).unwrap(), 2);
+        assert!(get_current_wallpaper_idx(&[], hm(12, 0)).is_err());
+    }
+
+    #[test]
+    fn config_round_trips_in_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            times: vec!["00:00".into(), "12:30".into()],
+            walls: vec!["/walls/night.png".into(), "/walls/day.png".into()],
+            mode: Some("fit".into()),
+            transition: Some(1.5),
+            ..Default::default()
+        };
+        for name in ["config.toml", "config.json", "config.yaml"] {
+            let path = dir.path().join(name);
+            write_config_to(&path, &config).unwrap();
+            let read = read_config(&path).unwrap();
+            assert_eq!(read.times, config.times, "{}", name);
+            assert_eq!(read.walls, config.walls, "{}", name);
+            assert_eq!(read.mode, config.mode, "{}", name);
+            assert_eq!(read.transition, config.transition, "{}", name);
+        }
+    }
+
+    #[test]
+    fn parse_weights_rejects_zero_and_garbage() {
+        assert_eq!(parse_weights("1, 1,6,2").unwrap(), [1, 1, 6, 2]);
+        assert!(parse_weights("1,0").is_err());
+        assert!(parse_weights("1,x").is_err());
+    }
+
+    #[test]
+    fn split_schedule_follows_the_weights() {
+        let split = SplitOptions {
+            weights: Some(vec![1, 2, 1]),
+            ..Default::default()
+        };
+        assert_eq!(split_schedule(walls(3), &split).unwrap().times, ["00:00", "06:00", "18:00"]);
+        // One weight per wallpaper
+        assert!(split_schedule(walls(2), &split).is_err());
+    }
+
+    #[test]
+    fn parse_window_takes_a_single_window() {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+        assert_eq!(parse_window("08:00-22:00").unwrap(), (hm(8, 0), hm(22, 0)));
+        assert!(parse_window("08:00-12:00,13:00-22:00").is_err());
+    }
+
+    #[test]
+    fn split_schedule_wraps_a_window_past_midnight() {
+        let split = SplitOptions {
+            window: Some(parse_window("22:00-02:00").unwrap()),
+            outside: Some("day.png".into()),
+            ..Default::default()
+        };
+        let config = split_schedule(walls(2), &split).unwrap();
+        assert_eq!(config.times, ["22:00", "00:00", "02:00"]);
+        assert_eq!(config.walls, ["0.png", "1.png", "day.png"]);
+    }
+
+    #[test]
+    fn parse_phases_checks_the_names() {
+        assert_eq!(parse_phases("Civil-Dawn, sunrise").unwrap(), ["civil-dawn", "sunrise"]);
+        let err = parse_phases("sunrise,brunch").unwrap_err();
+        assert!(err.to_string().contains("\"brunch\""), "{}", err);
+    }
+
+    #[test]
+    fn phases_config_needs_a_wallpaper_per_phase() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("1.png"), "").unwrap();
+        std::fs::write(dir.path().join("2.png"), "").unwrap();
+        let phases = parse_phases("sunrise,noon,sunset").unwrap();
+        assert!(phases_config(dir.path(), 51.5, -0.1, &phases).is_err());
+        let config = phases_config(dir.path(), 51.5, -0.1, &phases[..2]).unwrap();
+        assert_eq!(config.times.len(), 2);
+        assert_eq!(config.phases.unwrap(), ["sunrise", "noon"]);
+    }
+
+    #[test]
+    fn polar_config_shows_one_set_all_day() {
+        let day = vec!["day1.png".to_string(), "day2.png".to_string()];
+        let night = vec!["night.png".to_string()];
+        let config = polar_config(day.clone(), night.clone(), true, 78.0, 15.6).unwrap();
+        assert_eq!(config.walls, day);
+        assert_eq!(config.times, ["00:00", "12:00"]);
+        let config = polar_config(day, night.clone(), false, 78.0, 15.6).unwrap();
+        assert_eq!(config.walls, night);
+    }
+
+    #[test]
+    fn has_tag_ignores_case() {
+        assert!(has_tag("/walls/Day-01.png", DAY_TAG));
+        assert!(has_tag("/walls/01-NIGHT.png", "night"));
+        assert!(!has_tag("/walls/dusk.png", DAY_TAG));
+    }
+
+    #[test]
+    fn solar_dir_config_finds_mixed_case_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Day1.png", "DAY2.png", "night1.png", "NiGhT2.png"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let config = solar_dir_config(dir.path(), 51.5, -0.1, DAY_TAG, NIGHT_TAG).unwrap();
+        let names: Vec<String> = config
+            .walls
+            .iter()
+            .map(|w| Path::new(w).file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["Day1.png", "DAY2.png", "night1.png", "NiGhT2.png"]);
+    }
 }
@@ -0,0 +1,92 @@
+// THIS MODULE SENDS LOG MESSAGES TO STDERR AND OPTIONALLY TO A FILE
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Size at which the log file is moved to `flowy.log.1` and started again
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// Log file in the config directory, kept below `MAX_LOG_SIZE`
+struct LogFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    /// Appends a line, first rotating the file if it has grown too large
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.file.metadata()?.len() >= MAX_LOG_SIZE {
+            let mut backup = self.path.clone().into_os_string();
+            backup.push(".1");
+            std::fs::rename(&self.path, backup)?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        writeln!(self.file, "{}", line)
+    }
+}
+
+struct Logger {
+    stderr: env_logger::Logger,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata) || (self.file.is_some() && metadata.level() <= LevelFilter::Info)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+        // The file records what happened whatever is shown on stderr
+        if let (Some(file), true) = (&self.file, record.level() <= LevelFilter::Info) {
+            let line = format!("{} {:<5} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), record.level(), record.args());
+            if let Ok(mut file) = file.lock() {
+                let _ = file.write_line(&line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Returns the path of the log file written with `--log-file`
+pub fn get_log_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(crate::get_config_dir()?.join("flowy.log"))
+}
+
+/// Sets up logging to stderr at the level of RUST_LOG, or at `level` if given.
+/// With `log_file`, messages up to info are also appended to `flowy.log`
+/// in the config directory with the local time.
+pub fn init_logging(level: Option<LevelFilter>, log_file: bool) -> Result<(), Box<dyn Error>> {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if let Some(level) = level {
+        builder.filter_level(level);
+    }
+    let stderr = builder.format_target(false).build();
+    let file = match log_file {
+        true => Some(Mutex::new(LogFile::open(get_log_path()?)?)),
+        false => None,
+    };
+    let mut max_level = stderr.filter();
+    if file.is_some() {
+        max_level = max_level.max(LevelFilter::Info);
+    }
+    log::set_boxed_logger(Box::new(Logger { stderr, file }))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
@@ -1,44 +1,285 @@
 // CLI Import
-use clap::{load_yaml, App};
-use std::path::Path;
+use clap::{Clap, IntoApp};
+use cli::{CacheCommand, Cli, Command};
+use log::info;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+mod cli;
+mod completions;
 mod presets;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Housekeeping for Clap Arg parsing
-    let yaml = load_yaml!("cli.yml");
-    let matches = App::from(yaml).get_matches();
+    let cli = Cli::parse();
+    // Messages go to stderr, at the level of RUST_LOG unless --verbose or --quiet is given
+    let level = match (cli.verbose, cli.quiet) {
+        (true, _) => Some(log::LevelFilter::Debug),
+        (_, true) => Some(log::LevelFilter::Error),
+        _ => None,
+    };
+    flowy::init_logging(level, cli.log_file)?;
+    // Tools such as feh or xwallpaper the user wants tried first
+    if let Some(tools) = cli.prefer_tool.as_deref() {
+        let tools: Vec<_> = tools.split(',').collect();
+        wallpaper_rs::set_preferred_tools(&tools);
+    }
+    // Printing a completion script for the shell
+    if let Some(Command::Completions { shell }) = &cli.command {
+        return completions::print_completions(&Cli::into_app(), shell);
+    }
+    if cli.appearance && !flowy::APPEARANCE_SUPPORTED {
+        return Err("--appearance only works on macOS".into());
+    }
+    // Config kept outside of flowy's config folder
+    if let Some(path) = cli.config.as_deref() {
+        flowy::set_config_path(Path::new(path));
+    }
+    // Config stored in a table of a shared file
+    if let Some(section) = cli.config_section.as_deref() {
+        flowy::set_config_section(section);
+    }
+    if let Some(format) = cli.config_format.as_deref() {
+        flowy::set_config_format(format.parse()?);
+    }
+    // Printing an example config for new users
+    if cli.config_template {
+        print!("{}", flowy::config_template()?);
+        return Ok(());
+    }
+    // How wallpaper directories are read
+    let mut scan_options = flowy::ScanOptions::default();
+    if let Some(order) = cli.sort.as_deref() {
+        scan_options.sort = order.parse()?;
+    }
+    if cli.shuffle {
+        scan_options.sort = flowy::SortOrder::Random;
+    }
+    scan_options.seed = cli.seed;
+    scan_options.resolve_symlinks = cli.resolve_symlinks;
+    scan_options.recursive = cli.recursive;
+    scan_options.validate_images = cli.validate_images;
+    scan_options.convert = cli.convert;
+    flowy::set_scan_options(scan_options);
+    if let Some(location) = &cli.print_sun_now {
+        flowy::print_sun_now(location[0], location[1], cli.json);
+        return Ok(());
+    }
+    // Making a first config from the usual wallpaper folders
+    if let Some(Command::Init { force }) = &cli.command {
+        return flowy::init(*force);
+    }
+    // Showing what was detected, for bug reports
+    if cli.list_environments {
+        return flowy::print_environment();
+    }
+    // Managing the cache
+    if let Some(Command::Cache { command }) = &cli.command {
+        if let Some(CacheCommand::Clean { older_than }) = command {
+            let older_than = older_than.map(|days| Duration::from_secs(days * 24 * 60 * 60));
+            return flowy::clean_cache(older_than);
+        }
+        return flowy::show_cache();
+    }
+    // Adopting a config file from elsewhere
+    if let Some(source) = cli.copy_config_from.as_deref() {
+        let contents = if source.starts_with("http://") || source.starts_with("https://") {
+            let download = flowy::get_config_dir()?.join("download.toml");
+            presets::get_file(&download, source)?;
+            let contents = std::fs::read_to_string(&download);
+            std::fs::remove_file(&download)?;
+            contents?
+        } else {
+            std::fs::read_to_string(source)?
+        };
+        flowy::install_config(&contents, source)?;
+        info!("Copied the config from {}", source);
+        return Ok(());
+    }
+    // Pinning a single wallpaper instead of following a schedule
+    if let Some(image) = cli.set_and_watch.as_deref() {
+        return flowy::set_and_watch(Path::new(image));
+    }
+    // Controlling an already running daemon
+    if cli.pause {
+        flowy::pause()?;
+        info!("Paused flowy");
+        return Ok(());
+    }
+    if cli.resume {
+        flowy::resume()?;
+        info!("Resumed flowy");
+        return Ok(());
+    }
     // The times are set by themselves
     // Just supply the path and the TOML file is generated
-    let dir = matches.value_of("dir");
-    let preset = matches.value_of("preset");
+    let dir = cli.dir.as_deref();
+    let preset = cli.preset.as_deref();
+    // Wallpapers reused from another config instead of a directory
+    let images_from_config = cli.images_from_config.as_deref();
+    // Solar[0] - Directory
+    // Solar[1,2] - Lat Long
+    let solar: Option<Vec<_>> = cli.solar.as_ref().map(|solar| solar.iter().map(String::as_str).collect());
+    let location = match &solar {
+        Some(solar) => Some(solar_location(solar, cli.geo_auto)?),
+        None => None,
+    };
+    let phases = cli.phases.as_deref().map(flowy::parse_phases).transpose()?;
+    let day_tag = cli.day_tag.as_deref().unwrap_or(flowy::DAY_TAG);
+    let night_tag = cli.night_tag.as_deref().unwrap_or(flowy::NIGHT_TAG);
     // Error checking for the Solar option
-    if let Some(_) = matches.values_of("solar") {
-        // Loading up the args into a vector
-        // Solar[0] - Directory
-        // Solar[1,2] - Lat Long
-        let solar: Vec<_> = matches.values_of("solar").unwrap().collect();
-        flowy::generate_config_solar(
+    if let (Some(solar), Some((lat, long))) = (&solar, location) {
+        match images_from_config {
+            // Setting the wallpapers of DIR at the chosen solar events
+            _ if phases.is_some() => {
+                let phases = phases.as_deref().unwrap_or_default();
+                flowy::generate_config_phases(Path::new(solar[0]), lat, long, phases)?;
+            }
+            Some(other) => {
+                let walls = flowy::get_walls_from_config(Path::new(other))?;
+                let day_walls = walls.iter().filter(|w| flowy::has_tag(w, day_tag)).cloned().collect();
+                let night_walls = walls.iter().filter(|w| flowy::has_tag(w, night_tag)).cloned().collect();
+                flowy::generate_config_solar_from_walls(day_walls, night_walls, lat, long)?;
+            }
             // Passing the Directory
-            Path::new(solar[0]),
-            // Passing the lat long
-            solar[1].parse::<f64>().unwrap(),
-            solar[2].parse::<f64>().unwrap(),
-        )?;
+            None => flowy::generate_config_solar(Path::new(solar[0]), lat, long, day_tag, night_tag)?,
+        }
+    } else if let Some(other) = images_from_config {
+        flowy::generate_config_from_walls(flowy::get_walls_from_config(Path::new(other))?)?;
+    } else if let Some(other) = cli.from_wallpaper_of.as_deref() {
+        flowy::generate_config_from_walls(flowy::import_walls(Path::new(other))?)?;
     }
     // Since the functions are not required, this checks if
     // arguments have been passed to flowy
     // along with some error handling
-    match flowy::match_dir(dir) {
-        Ok(_) => (),
-        Err(e) => eprintln!("Error with dir {}", e),
-    }
-    match presets::match_preset(preset) {
-        Ok(_) => (),
-        Err(e) => eprintln!("Error with preset {}", e),
+    let elevation_steps = cli.elevation_steps.as_ref();
+    let split = flowy::SplitOptions {
+        weights: cli.weights.as_deref().map(flowy::parse_weights).transpose()?,
+        window: cli.window.as_deref().map(flowy::parse_window).transpose()?,
+        outside: match cli.outside_wallpaper.as_deref() {
+            Some(wall) => Some(flowy::wall_from_path(Path::new(wall))?),
+            None => None,
+        },
+    };
+    match (dir, cli.by_color_temp, elevation_steps) {
+        (Some(dir), _, Some(location)) => {
+            flowy::generate_config_elevation(Path::new(dir), location[0], location[1])?;
+        }
+        (Some(dir), by_color_temp, None) if by_color_temp || split.weights.is_some() || split.window.is_some() => {
+            let mut walls = flowy::get_dir(Path::new(dir), "")?;
+            if by_color_temp {
+                walls = flowy::sort_by_color_temp(walls)?;
+            }
+            flowy::generate_config_split(walls, &split)?;
+        }
+        _ => flowy::match_dir(dir)?,
     }
+    presets::match_preset(preset, cli.preset_sha256.as_deref())?;
     // Runs forever
     let config = flowy::get_config()?;
-    flowy::set_times(config)?;
+    // Checking the config instead of running the daemon
+    if let Some(Command::VerifyConfig) = &cli.command {
+        return flowy::verify_config(&config);
+    }
+    // Showing what the schedule does now instead of running the daemon
+    if let Some(Command::Status) = &cli.command {
+        return flowy::print_status(&config);
+    }
+    // Setting the wallpaper once instead of running the daemon
+    if cli.apply {
+        return flowy::apply(&config);
+    }
+    // Showing the schedule instead of running the daemon
+    if cli.dry_run {
+        return flowy::print_schedule(&config);
+    }
+    // Comparing the desktop with the schedule instead of running the daemon
+    if cli.audit {
+        return flowy::audit(&config);
+    }
+    // Printing the wallpaper for now instead of running the daemon
+    if cli.current {
+        return flowy::print_current(&config, cli.explain);
+    }
+    // Timing the desktop instead of running the daemon
+    if let Some(runs) = cli.benchmark {
+        return flowy::benchmark(&config, runs.unwrap_or(5));
+    }
+    // Exporting the schedule for other tools
+    if let Some(file) = cli.dump_schedule.as_deref() {
+        flowy::dump_schedule(&config, Path::new(file))?;
+        info!("Schedule written to {}", file);
+    }
+    let mut options = flowy::DaemonOptions::default();
+    if let Some(fits) = cli.fit_per_monitor.as_deref() {
+        options.fit_per_monitor = flowy::parse_fit_per_monitor(fits)?;
+    }
+    options.mode = cli.fit.as_deref().map(str::parse).transpose()?;
+    options.transition = cli.transition.map(flowy::parse_transition).transpose()?;
+    options.monitor_hotplug = cli.monitor_hotplug;
+    options.appearance = cli.appearance;
+    if let Some(windows) = cli.active_hours.as_deref() {
+        options.active_hours = flowy::parse_active_hours(windows)?;
+    }
+    options.holiday_file = cli.holiday_file.as_deref().map(PathBuf::from);
+    if let Some(interval) = cli.interval.as_deref() {
+        let interval = flowy::parse_duration(interval)?;
+        if interval.is_zero() {
+            return Err("The interval must be longer than 0 seconds".into());
+        }
+        options.interval = Some(interval);
+    }
+    // Keeping the sunrise and sunset times up to date
+    if let (Some(solar), Some((lat, long)), None) = (&solar, location, images_from_config) {
+        let interval = match cli.solar_update_interval.as_deref() {
+            Some(interval) => Some(flowy::parse_duration(interval)?),
+            None => None,
+        };
+        if interval.is_some_and(|i| i < flowy::MIN_SOLAR_UPDATE_INTERVAL) {
+            return Err("The solar update interval must be at least 5 minutes".into());
+        }
+        options.solar_update = Some(flowy::SolarUpdate {
+            kind: match phases {
+                Some(_) => flowy::ScheduleKind::Phases,
+                None => flowy::ScheduleKind::Solar,
+            },
+            dir: solar[0].into(),
+            lat,
+            long,
+            phases: phases.unwrap_or_default(),
+            day_tag: day_tag.to_string(),
+            night_tag: night_tag.to_string(),
+            interval,
+        });
+    }
+    if let Some(steps) = cli.morph {
+        let steps = steps.unwrap_or(10);
+        if steps == 0 {
+            return Err("The number of morph steps must be at least 1".into());
+        }
+        options.morph_steps = Some(steps);
+    }
+    flowy::set_times(config, &options)?;
     // Never reaches this but needed for Result return
     Ok(())
 }
+
+/// Returns the location passed to --solar after the directory. With
+/// --geo-auto it may be left out, and is then read from the config or
+/// looked up from the IP address.
+fn solar_location(solar: &[&str], geo_auto: bool) -> Result<(f64, f64), Box<dyn std::error::Error>> {
+    match solar {
+        [_, lat, long] => Ok((lat.parse()?, long.parse()?)),
+        [_] if geo_auto => {
+            // Looked up once and then kept in the config
+            let saved = flowy::get_config().ok().and_then(|c| Some((c.lat?, c.long?)));
+            if let Some((lat, long)) = saved {
+                info!("Using the location saved in the config (Lat: {} Long: {})", lat, long);
+                return Ok((lat, long));
+            }
+            flowy::geolocate().map_err(|e| {
+                format!("Could not detect your location ({}). Pass LAT and LONG to --solar instead", e).into()
+            })
+        }
+        _ => Err("--solar needs DIR LAT LONG, or only DIR with --geo-auto".into()),
+    }
+}
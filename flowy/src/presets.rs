@@ -1,62 +1,289 @@
 use flate2::read::GzDecoder;
+use log::{debug, info};
+use sha2::{Digest, Sha256};
 use std::error::Error;
 use std::fs::File;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Component, Path};
 use tar::Archive;
 
-/// Downloads a given file
-pub fn get_file(path: &Path, url: &str) -> Result<(), Box<dyn Error>> {
-    println!("GET file");
+/// Bytes between progress updates when the size of a download is unknown
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Downloads a given file and returns its SHA-256 digest in hex
+pub fn get_file(path: &Path, url: &str) -> Result<String, Box<dyn Error>> {
+    debug!("GET file");
     let res = ureq::get(url).call()?;
-    println!("Status: {}", res.status());
+    debug!("Status: {}", res.status());
+    let length: Option<u64> = res.header("Content-Length").and_then(|l| l.parse().ok());
+    // Progress is left out when only warnings and errors are logged
+    let progress = log::log_enabled!(log::Level::Info);
     let mut reader = res.into_reader();
-    let mut out = File::create(path).expect("Failed to create file");
-    std::io::copy(&mut reader, &mut out).expect("Failed to copy content");
-    println!("Tar ball downloaded");
-    Ok(())
+    let mut out = File::create(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    // Progress goes to stderr so that stdout stays clean for scripts
+    let (mut downloaded, mut shown) = (0, None);
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        out.write_all(&buffer[..read])?;
+        downloaded += read as u64;
+        match length {
+            Some(length) if length > 0 => {
+                let percent = downloaded * 100 / length;
+                if progress && shown != Some(percent) {
+                    shown = Some(percent);
+                    eprint!("\rDownloading: {}%", percent);
+                }
+            }
+            // Without a length, every MB downloaded is shown
+            _ => {
+                let mb = downloaded / PROGRESS_STEP;
+                if progress && shown != Some(mb) {
+                    shown = Some(mb);
+                    eprint!("\rDownloading: {} KB", downloaded / 1024);
+                }
+            }
+        }
+    }
+    if progress {
+        eprintln!();
+    }
+    info!("File downloaded");
+    Ok(to_hex(&hasher.finalize()))
 }
 
-/// Unpacks a tar ball to a new directory
+/// Returns bytes as lowercase hex
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Checks the digest of a downloaded archive against the expected one,
+/// ignoring case. A mismatched archive is deleted.
+fn verify_sha256(archive_path: &Path, digest: &str, expected: Option<&str>) -> Result<(), Box<dyn Error>> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(digest) => {
+            std::fs::remove_file(archive_path)?;
+            Err(format!(
+                "The preset's SHA-256 is {} but {} was expected, it may be corrupted",
+                digest, expected
+            )
+            .into())
+        }
+        Some(_) => {
+            info!("SHA-256 verified");
+            Ok(())
+        }
+        None => {
+            info!("SHA-256: {}", digest);
+            Ok(())
+        }
+    }
+}
+
+/// Unpacks a tar ball to a new directory. Nothing is unpacked if any
+/// entry would end up outside of `dst`.
 fn unpack_tar(src: &Path, dst: &Path) -> Result<(), Box<dyn Error>> {
-    println!("Unpacking tar ball {:?}", &src);
+    info!("Unpacking tar ball {:?}", &src);
+    check_tar(src)?;
     let tar_gz = File::open(src)?;
     let tar = GzDecoder::new(tar_gz);
     let mut archive = Archive::new(tar);
     archive.unpack(dst)?;
-    println!("Done");
+    debug!("Done");
+    Ok(())
+}
+
+/// Checks that every entry of a tar ball, and the target of every link
+/// in it, is a relative path which doesn't climb out with `..`
+fn check_tar(src: &Path) -> Result<(), Box<dyn Error>> {
+    let stays_inside = |path: &Path| {
+        path.components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    };
+    let mut archive = Archive::new(GzDecoder::new(File::open(src)?));
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let path = entry.path()?;
+        if !stays_inside(&path) {
+            return Err(format!("Refusing to unpack {}, it is outside the preset folder", path.display()).into());
+        }
+        if let Some(target) = entry.link_name()? {
+            // Hard links are relative to the tar ball, symlinks to the folder of the link
+            let resolved = match entry.header().entry_type().is_hard_link() {
+                true => target.to_path_buf(),
+                false => path.parent().unwrap_or(Path::new("")).join(&target),
+            };
+            let depth = resolved.components().try_fold(0i32, |depth, c| match c {
+                Component::Normal(_) => Some(depth + 1),
+                Component::CurDir => Some(depth),
+                Component::ParentDir if depth > 0 => Some(depth - 1),
+                _ => None,
+            });
+            if depth.is_none() {
+                return Err(format!(
+                    "Refusing to unpack {}, it links to {} outside the preset folder",
+                    path.display(),
+                    target.display()
+                )
+                .into());
+            }
+        }
+    }
     Ok(())
 }
 
-/// Matches the agrguments passed with preset flag
-pub fn match_preset(preset: Option<&str>) -> Result<(), Box<dyn Error>> {
+/// A preset which can be picked by name
+struct Preset {
+    name: &'static str,
+    url: &'static str,
+    /// SHA-256 digest of the tar ball in hex, if it is known
+    sha256: Option<&'static str>,
+}
+
+/// Presets which can be picked by name
+const PRESETS: &[Preset] = &[Preset {
+    name: "lake",
+    url: "https://bucket-more.s3.ap-south-1.amazonaws.com/uploads/lake.tar.gz",
+    sha256: None,
+}];
+
+/// Returns the URL of a named preset, or the preset itself if it is the
+/// URL of a tar ball, along with the name of the folder it unpacks to
+fn preset_url(preset: &str) -> Result<(String, String), Box<dyn Error>> {
+    let url = match PRESETS.iter().find(|p| p.name == preset) {
+        Some(p) => p.url.to_string(),
+        None if preset.starts_with("http://") || preset.starts_with("https://") => preset.to_string(),
+        None => {
+            let names: Vec<_> = PRESETS.iter().map(|p| p.name).collect();
+            return Err(format!(
+                "Unknown preset {}, expected one of {} or the URL of a .tar.gz file",
+                preset,
+                names.join(", ")
+            )
+            .into());
+        }
+    };
+
+    // The folder is named after the tar ball, e.g. lake.tar.gz unpacks to lake
+    let (_, rest) = url.split_once("://").ok_or("Missing scheme")?;
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let file = match path.split_once('/') {
+        Some((host, file)) if !host.is_empty() => file.rsplit('/').next().unwrap_or_default(),
+        _ => return Err(format!("Malformed preset URL {}", url).into()),
+    };
+    let name = file
+        .strip_suffix(".tar.gz")
+        .or_else(|| file.strip_suffix(".tgz"))
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| format!("The preset URL {} must end in a .tar.gz file", url))?;
+    Ok((url.clone(), name.to_string()))
+}
+
+/// Matches the agrguments passed with preset flag.
+/// The preset is either the name of a known preset or the URL of a tar ball
+/// holding a folder of the same name, e.g. `night-city.tar.gz` holding `night-city/`.
+///
+/// The download is checked against `sha256`, or the known digest of a named preset.
+pub fn match_preset(preset: Option<&str>, sha256: Option<&str>) -> Result<(), Box<dyn Error>> {
     match preset {
         None => (),
-        // As can be seen here, we only check if
-        // there is something after the preset flag
-        Some(_) => {
+        Some(preset) => {
+            let (url, name) = preset_url(preset)?;
+            let expected = sha256.or_else(|| PRESETS.iter().find(|p| p.name == preset)?.sha256);
             let config_path = flowy::get_config_dir()?;
 
             let mut archive_path = config_path.clone();
-            archive_path.push("lake.tar.gz");
+            archive_path.push(format!("{}.tar.gz", name));
             let mut dir_path = config_path.clone();
-            dir_path.push("lake");
+            dir_path.push(&name);
 
             // Download and unzip the folder
-            get_file(
-                &archive_path,
-                "https://bucket-more.s3.ap-south-1.amazonaws.com/uploads/lake.tar.gz",
-            )?;
-            unpack_tar(&archive_path, &config_path).unwrap();
+            let digest = get_file(&archive_path, &url)?;
+            verify_sha256(&archive_path, &digest, expected)?;
+            let unpacked = unpack_tar(&archive_path, &config_path);
 
             // Deleting the tar ball
             std::fs::remove_file(&archive_path)?;
+            unpacked?;
+            if !dir_path.is_dir() {
+                return Err(format!("The preset did not contain a folder named {}", name).into());
+            }
 
             // A config file, config.toml must be generated now
             flowy::generate_config(&dir_path)?;
 
-            println!("Preset set successfully")
+            info!("Preset set successfully")
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 of the bytes `abc`
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn verify_sha256_accepts_the_known_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("abc.tar.gz");
+        std::fs::write(&archive, "abc").unwrap();
+        let digest = to_hex(&Sha256::digest(std::fs::read(&archive).unwrap()));
+        assert_eq!(digest, ABC_SHA256);
+        verify_sha256(&archive, &digest, Some(&ABC_SHA256.to_uppercase())).unwrap();
+        assert!(archive.exists());
+    }
+
+    #[test]
+    fn verify_sha256_deletes_a_mismatched_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("abc.tar.gz");
+        std::fs::write(&archive, "abd").unwrap();
+        let digest = to_hex(&Sha256::digest(std::fs::read(&archive).unwrap()));
+        assert!(verify_sha256(&archive, &digest, Some(ABC_SHA256)).is_err());
+        assert!(!archive.exists());
+    }
+
+    /// Writes a tar ball with one file entry. The name is written as it is,
+    /// as `tar` refuses to build entries with `..` in them.
+    fn write_tar(path: &Path, name: &str) {
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let gz = flate2::write::GzEncoder::new(File::create(path).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(gz);
+        builder.append(&header, &b"evil"[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn unpack_tar_refuses_entries_outside_the_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("preset.tar.gz");
+        write_tar(&archive, "../evil");
+        let dst = dir.path().join("presets");
+        std::fs::create_dir(&dst).unwrap();
+        let err = unpack_tar(&archive, &dst).unwrap_err();
+        assert!(err.to_string().contains("outside the preset folder"), "{}", err);
+        assert!(!dir.path().join("evil").exists());
+    }
+
+    #[test]
+    fn unpack_tar_unpacks_entries_inside_the_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("preset.tar.gz");
+        write_tar(&archive, "lake/01.png");
+        unpack_tar(&archive, dir.path()).unwrap();
+        assert!(dir.path().join("lake/01.png").is_file());
+    }
+}
@@ -0,0 +1,33 @@
+// THIS MODULE TELLS SYSTEMD ABOUT THE DAEMON WHEN BUILT WITH THE systemd FEATURE
+use std::time::Duration;
+
+/// Tells systemd that the daemon is up, for services with Type=notify
+pub(crate) fn ready() {
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Ready]);
+}
+
+/// Tells systemd that the daemon is shutting down
+pub(crate) fn stopping() {
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]);
+}
+
+/// Tells systemd's watchdog that the daemon is still running
+pub(crate) fn watchdog() {
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Watchdog]);
+}
+
+/// Longest the daemon may sleep between watchdog pings, half of the
+/// service's WatchdogSec, or `None` without a watchdog
+pub(crate) fn watchdog_interval() -> Option<Duration> {
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    {
+        let mut usec = 0;
+        if sd_notify::watchdog_enabled(false, &mut usec) {
+            return Some(Duration::from_micros(usec / 2));
+        }
+    }
+    None
+}
@@ -1,13 +1,13 @@
-///! This file is a copy of [solar.c](https://github.com/jonls/redshift/blob/master/src/solar.c)
-///! from redshift.
-///!
-///! This module makes extensive use of the
-///! [Julian Day notation](https://en.wikipedia.org/wiki/Julian_day)
-///! to measure elapsed days between events and in calculations.
-///!
-///! See also https://en.wikipedia.org/wiki/Sunrise_equation#Complete_calculation_on_Earth
-///!
-use chrono::{DateTime, Local, NaiveDateTime, Timelike, Utc};
+//! This file is a copy of [solar.c](https://github.com/jonls/redshift/blob/master/src/solar.c)
+//! from redshift.
+//!
+//! This module makes extensive use of the
+//! [Julian Day notation](https://en.wikipedia.org/wiki/Julian_day)
+//! to measure elapsed days between events and in calculations.
+//!
+//! See also https://en.wikipedia.org/wiki/Sunrise_equation#Complete_calculation_on_Earth
+//!
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use std::collections::HashMap;
 
 /* Ported from javascript code by U.S. Department of Commerce,
@@ -43,6 +43,7 @@ pub enum SolarTime {
 }
 
 impl SolarTime {
+    /// Iterates over every solar event
     pub fn iterator() -> impl Iterator<Item = SolarTime> {
         [
             SolarTime::Noon,
@@ -59,6 +60,39 @@ impl SolarTime {
         .iter()
         .copied()
     }
+
+    /// Name of the phase, as accepted by `from_name`
+    pub fn name(self) -> &'static str {
+        match self {
+            SolarTime::Noon => "noon",
+            SolarTime::Midnight => "midnight",
+            SolarTime::AstroDawn => "astro-dawn",
+            SolarTime::NautDawn => "naut-dawn",
+            SolarTime::CivilDawn => "civil-dawn",
+            SolarTime::Sunrise => "sunrise",
+            SolarTime::Sunset => "sunset",
+            SolarTime::CivilDusk => "civil-dusk",
+            SolarTime::NautDusk => "naut-dusk",
+            SolarTime::AstroDusk => "astro-dusk",
+        }
+    }
+
+    /// Parses a phase name such as `civil-dawn` or `sunset`
+    pub fn from_name(name: &str) -> Option<SolarTime> {
+        match name.trim().to_lowercase().as_str() {
+            "noon" => Some(SolarTime::Noon),
+            "midnight" => Some(SolarTime::Midnight),
+            "astro-dawn" => Some(SolarTime::AstroDawn),
+            "naut-dawn" => Some(SolarTime::NautDawn),
+            "civil-dawn" => Some(SolarTime::CivilDawn),
+            "sunrise" => Some(SolarTime::Sunrise),
+            "sunset" => Some(SolarTime::Sunset),
+            "civil-dusk" => Some(SolarTime::CivilDusk),
+            "naut-dusk" => Some(SolarTime::NautDusk),
+            "astro-dusk" => Some(SolarTime::AstroDusk),
+            _ => None,
+        }
+    }
 }
 
 /// A Julian day, i.e. the number of days since the beginning of the Julian Period
@@ -126,12 +160,42 @@ impl std::ops::Sub for JulianDay {
     }
 }
 
-#[derive(Debug, Default)]
+/// Times of the solar events of one day at a location, as Unix epochs in seconds.
+///
+/// Today's sunrise in Berlin:
+///
+/// ```
+/// use flowy::{SolarTime, Timetable};
+/// use std::time::{SystemTime, UNIX_EPOCH};
+///
+/// let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+/// let berlin = Timetable::new(now, 52.52, 13.40);
+/// match berlin.get(&SolarTime::Sunrise) {
+///     Some(sunrise) => println!("The sun rises {} seconds after 1970", sunrise),
+///     None => println!("The sun doesn't rise today"),
+/// }
+/// ```
+///
+/// Events which don't happen on that day, such as sunrise during polar night, are left out:
+///
+/// ```
+/// use flowy::{SolarTime, Timetable};
+///
+/// // Midsummer at the North Pole
+/// let tt = Timetable::new(1_687_348_800.0, 90.0, 0.0);
+/// assert!(tt.get(&SolarTime::Sunset).is_none());
+/// assert!(tt.sun_up_at_noon());
+/// ```
+#[derive(Debug, Default, Clone)]
 pub struct Timetable {
     angles: HashMap<SolarTime, f64>,
     date: f64,
     lat: f64,
     lon: f64,
+    /// The rounded Julian day `timetable` was generated for. Every time
+    /// within that day shares the same table, so it is only generated again
+    /// when the date moves to another day.
+    day: Option<JulianDay>,
     timetable: HashMap<SolarTime, f64>,
 }
 
@@ -171,7 +235,8 @@ impl Timetable {
     }
 
     /// Generates a `Map<SolarTime, f64>` which contains for all solar events the epoch (seconds)
-    /// at which they will occur, given the current date, latitude and longitude
+    /// at which they will occur, given the current date, latitude and longitude.
+    /// Events which do not happen on that day, e.g. sunrise during polar night, are left out.
     fn generate_timetable(&self) -> HashMap<SolarTime, f64> {
         let mut ret: HashMap<SolarTime, f64> = HashMap::new();
 
@@ -185,12 +250,16 @@ impl Timetable {
         // Calculate apparent solar noon
         let sol_noon: f64 = time_of_solar_noon(century, self.lon);
         let j_noon = jdn.sub(0.5).add(sol_noon / MINS_PER_DAY);
-        let t_noon: f64 = j_noon.century();
+        let noon = SunAt::new(j_noon.century());
 
         // Calulate absolute time of other phenomena
         for st in SolarTime::iterator() {
             let angle: f64 = self.angles.get(&st).unwrap_or(&0.0).to_owned();
-            let offset: f64 = time_of_solar_elevation(century, t_noon, self.lat, self.lon, angle);
+            let offset: f64 = time_of_solar_elevation(century, noon, self.lat, self.lon, angle);
+            // The sun never reaches this elevation today
+            if offset.is_nan() {
+                continue;
+            }
             ret.insert(st, jdn.sub(0.5).add(offset / MINS_PER_DAY).epoch());
         }
 
@@ -210,10 +279,18 @@ impl Timetable {
     pub fn new(date: f64, lat: f64, lon: f64) -> Self {
         let mut ret = Self::default();
         ret.angles = ret.generate_time_angles();
-        ret.date = date;
         ret.lat = lat;
         ret.lon = lon;
-        ret.timetable = ret.generate_timetable();
+        ret.set_date(date);
+
+        ret
+    }
+
+    /// Returns the timetable of another date at the same coordinates,
+    /// reusing the angles and, on the same day, the generated times
+    pub fn with_same_location(&self, date: f64) -> Self {
+        let mut ret = self.clone();
+        ret.set_date(date);
 
         ret
     }
@@ -225,21 +302,64 @@ impl Timetable {
     }
 
     /// Simple utility function to retrieve only sunset and sunrise times
-    /// Returns a tuple (sunrise, sunset) as i64
-    pub fn get_sunrise_sunset(&self) -> (i64, i64) {
+    /// Returns a tuple (sunrise, sunset) as i64, or `None` during polar day or night
+    pub fn get_sunrise_sunset(&self) -> Option<(i64, i64)> {
         // Index into the HashMap using SolarTime Enum
-        let sunrise: i64 = self.timetable.get(&SolarTime::Sunrise).unwrap().round() as i64;
-        let sunset: i64 = self.timetable.get(&SolarTime::Sunset).unwrap().round() as i64;
+        let sunrise: i64 = self.timetable.get(&SolarTime::Sunrise)?.round() as i64;
+        let sunset: i64 = self.timetable.get(&SolarTime::Sunset)?.round() as i64;
 
         // Return tuple of sunsrise and sunset times
-        (sunrise, sunset)
+        Some((sunrise, sunset))
     }
 
-    /// Sets a new date for the timetable and regenerates it with the same coordinates
+    /// Whether the sun is above the horizon at solar noon, which during
+    /// polar day and night tells whether it is up all day or not at all
+    pub fn sun_up_at_noon(&self) -> bool {
+        let noon: f64 = self.timetable.get(&SolarTime::Noon).copied().unwrap_or(self.date);
+        solar_elevation(noon, self.lat, self.lon) >= DAYTIME_ELEV
+    }
+
+    /// Whether the sun is above the horizon at `epoch` (seconds) on this day,
+    /// i.e. between sunrise and sunset
+    pub fn sun_up_at(&self, epoch: f64) -> bool {
+        match self.get_sunrise_sunset() {
+            Some((sunrise, sunset)) if sunrise <= sunset => (sunrise as f64) <= epoch && epoch < sunset as f64,
+            // Far from Greenwich the sun may set before it rises on the same UTC day
+            Some((sunrise, sunset)) => epoch >= sunrise as f64 || epoch < sunset as f64,
+            None => self.sun_up_at_noon(),
+        }
+    }
+
+    /// Returns the first solar event strictly after `after_epoch` (seconds) at the same
+    /// coordinates, with its time in epoch seconds, looking at the following days if needed
+    pub fn next_event(&self, after_epoch: f64) -> (SolarTime, f64) {
+        // The day before ends with its solar midnight, which may fall after `after_epoch`
+        let mut date: f64 = after_epoch - SECS_PER_DAY;
+        loop {
+            let tt = self.with_same_location(date);
+            let next = tt
+                .timetable
+                .iter()
+                .filter(|(_, time)| **time > after_epoch)
+                .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal));
+            // Noon and midnight happen every day, so this ends by the next day
+            if let Some((st, time)) = next {
+                return (*st, *time);
+            }
+            date += SECS_PER_DAY;
+        }
+    }
+
+    /// Sets a new date for the timetable and regenerates it with the same coordinates,
+    /// unless the date is on the day it was generated for
     /// - epoch: a Unix epoch in seconds
     pub fn set_date(&mut self, epoch: f64) {
         self.date = epoch;
-        self.timetable = self.generate_timetable();
+        let day = JulianDay::from_epoch(epoch).round();
+        if self.day != Some(day) {
+            self.timetable = self.generate_timetable();
+            self.day = Some(day);
+        }
     }
 
     /// Returns a rough (but decently precise) number of minutes passed since the last midnight event
@@ -353,6 +473,26 @@ fn equation_of_time(century: f64) -> f64 {
     4.0 * eq_result.to_degrees()
 }
 
+/// The equation of time and the declination of the sun at one moment, which
+/// are the same for every event of a day on the first pass of
+/// `time_of_solar_elevation`, so they are only calculated once per day
+#[derive(Debug, Copy, Clone)]
+struct SunAt {
+    /// Result of `equation_of_time`, in minutes
+    eq_time: f64,
+    /// Result of `solar_declination`, in radians
+    decl: f64,
+}
+
+impl SunAt {
+    fn new(century: f64) -> Self {
+        Self {
+            eq_time: equation_of_time(century),
+            decl: solar_declination(century),
+        }
+    }
+}
+
 /// Calculates the hour angle (in radians) at the location for the given angular elevation.
 /// - lat: Latitude of location in degrees
 /// - decl: Declination in radians
@@ -400,16 +540,14 @@ fn time_of_solar_noon(century: f64, lon: f64) -> f64 {
 /// Calculates the time of given apparent solar angular elevation of location on earth.
 /// Returns the time difference from mean solar midnight in minutes.
 /// - century: Julian centuries since J2000.0
-/// - t_noon: Apparent solar noon in Julian centuries since J2000.0
+/// - noon: The sun at apparent solar noon
 /// - lat: Latitude of location in degrees
 /// - lon: Longtitude of location in degrees
 /// - elev: Solar angular elevation in radians
-fn time_of_solar_elevation(century: f64, t_noon: f64, lat: f64, lon: f64, elev: f64) -> f64 {
-    // First pass uses approximate sunrise to calculate equation of time
-    let eq_time: f64 = equation_of_time(t_noon);
-    let sol_decl: f64 = solar_declination(t_noon);
-    let ha: f64 = hour_angle_from_elevation(lat, sol_decl, elev);
-    let sol_offset: f64 = 720.0 - 4.0 * (lon + ha.to_degrees()) - eq_time;
+fn time_of_solar_elevation(century: f64, noon: SunAt, lat: f64, lon: f64, elev: f64) -> f64 {
+    // First pass uses the sun at noon to approximate the time
+    let ha: f64 = hour_angle_from_elevation(lat, noon.decl, elev);
+    let sol_offset: f64 = 720.0 - 4.0 * (lon + ha.to_degrees()) - noon.eq_time;
 
     // Recalculate using new sunrise
     let t_rise: f64 = JulianDay::from_century(century)
@@ -444,6 +582,12 @@ fn solar_elevation_from_time(century: f64, lat: f64, lon: f64) -> f64 {
 /// - lat: Latitude of location
 /// - lon: Longitude of location
 /// - Return: Solar angular elevation in degrees
+///
+/// ```
+/// // The sun is high over the equator at noon on the March equinox
+/// let elevation = flowy::solar_elevation(1_679_313_600.0, 0.0, 0.0);
+/// assert!(elevation > 85.0);
+/// ```
 pub fn solar_elevation(epoch: f64, lat: f64, lon: f64) -> f64 {
     let jd = JulianDay::from_epoch(epoch);
     let ret: f64 = solar_elevation_from_time(jd.century(), lat, lon);
@@ -451,6 +595,23 @@ pub fn solar_elevation(epoch: f64, lat: f64, lon: f64) -> f64 {
     ret.to_degrees()
 }
 
+/// Names the part of the day for a solar elevation (in degrees).
+/// - rising: Whether the sun is rising, telling dawn from dusk
+pub fn sun_phase(elevation: f64, rising: bool) -> &'static str {
+    let twilight = |dawn, dusk| if rising { dawn } else { dusk };
+    if elevation >= DAYTIME_ELEV {
+        "Day"
+    } else if elevation >= CIVIL_TWILIGHT_ELEV {
+        twilight("Civil Dawn", "Civil Dusk")
+    } else if elevation >= NAUT_TWILIGHT_ELEV {
+        twilight("Nautical Dawn", "Nautical Dusk")
+    } else if elevation >= ASTRO_TWILIGHT_ELEV {
+        twilight("Astronomical Dawn", "Astronomical Dusk")
+    } else {
+        "Night"
+    }
+}
+
 /// Converts UNIX seconds to a human readable format (HH:MM:ss)
 /// - time: absolute datetime (in epoch seconds) to convert
 pub fn unix_to_local(time: i64) -> DateTime<Local> {
@@ -463,9 +624,56 @@ pub fn unix_to_local(time: i64) -> DateTime<Local> {
     converted
 }
 
-pub fn time_to_minutes(time: String) -> u32 {
-    let time = chrono::NaiveTime::parse_from_str(&time, "%H:%M:%S").unwrap();
-    let h1 = time.hour();
-    let m1 = time.minute();
-    h1 * 60 + m1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-03-20 and 2024-06-21, 12:00 UTC
+    const EQUINOX: f64 = 1_710_936_000.0;
+    const MIDSUMMER: f64 = 1_718_971_200.0;
+    /// 2024-12-21, 12:00 UTC
+    const MIDWINTER: f64 = 1_734_782_400.0;
+
+    #[test]
+    fn sunrise_moves_between_dates() {
+        // Seconds after midnight UTC of London's sunrise
+        let sunrise = |date| Timetable::new(date, 51.5, -0.1).get_sunrise_sunset().unwrap().0.rem_euclid(86400);
+        // About 06:00 in March and 03:45 in June
+        assert!(sunrise(EQUINOX) - sunrise(MIDSUMMER) > 2 * 3600);
+    }
+
+    #[test]
+    fn polar_day_and_night_at_78_degrees() {
+        let june = Timetable::new(MIDSUMMER, 78.0, 15.6);
+        assert_eq!(june.get_sunrise_sunset(), None);
+        assert!(june.sun_up_at_noon());
+        assert!(june.sun_up_at(MIDSUMMER + 12.0 * 3600.0));
+
+        let december = Timetable::new(MIDWINTER, 78.0, 15.6);
+        assert_eq!(december.get_sunrise_sunset(), None);
+        assert!(!december.sun_up_at_noon());
+        // Noon and midnight still happen
+        assert!(december.get(&SolarTime::Noon).is_some());
+        assert!(december.get(&SolarTime::Midnight).is_some());
+    }
+
+    #[test]
+    fn next_event_around_noon() {
+        let tt = Timetable::new(EQUINOX, 51.5, -0.1);
+        let noon = *tt.get(&SolarTime::Noon).unwrap();
+        assert_eq!(tt.next_event(noon - 1.0), (SolarTime::Noon, noon));
+        // Strictly after, so noon itself is passed
+        assert_eq!(tt.next_event(noon).0, SolarTime::Sunset);
+    }
+
+    #[test]
+    fn next_event_just_before_midnight() {
+        let tt = Timetable::new(EQUINOX, 51.5, -0.1);
+        let midnight = *tt.get(&SolarTime::Midnight).unwrap();
+        assert_eq!(tt.next_event(midnight - 1.0), (SolarTime::Midnight, midnight));
+        // The next one is in the following day's table
+        let (event, time) = tt.next_event(midnight);
+        assert_eq!(event, SolarTime::AstroDawn);
+        assert!(time > midnight && time - midnight < SECS_PER_DAY);
+    }
 }
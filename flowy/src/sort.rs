@@ -0,0 +1,165 @@
+// THIS MODULE HANDLES THE ORDERING OF THE WALLPAPERS
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// Order in which the wallpapers of a directory are shown
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum SortOrder {
+    /// By name, ignoring case
+    Name,
+    /// By name, comparing runs of digits as numbers so that 2.jpg comes before 10.jpg.
+    /// Zero padded names such as 00.jpg, 01.jpg keep their order.
+    #[default]
+    Natural,
+    /// By modification time, newest last
+    Mtime,
+    /// In a random order
+    Random,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "name" => Ok(SortOrder::Name),
+            "natural" => Ok(SortOrder::Natural),
+            "mtime" => Ok(SortOrder::Mtime),
+            "random" => Ok(SortOrder::Random),
+            _ => Err(format!(
+                "Unknown sort order {}, expected name, natural, mtime or random",
+                s
+            )),
+        }
+    }
+}
+
+/// Sorts wallpapers in the given order. Ties are broken by the raw name
+/// so that the result doesn't depend on the system. A random order is
+/// the same each time for the same `seed`.
+pub fn sort_walls(walls: &mut Vec<String>, order: SortOrder, seed: Option<u64>) -> Result<(), Box<dyn Error>> {
+    match order {
+        SortOrder::Name => walls.sort_by(|a, b| {
+            a.to_lowercase()
+                .cmp(&b.to_lowercase())
+                .then_with(|| a.cmp(b))
+        }),
+        SortOrder::Natural => walls.sort_by(|a, b| natural_cmp(a, b).then_with(|| a.cmp(b))),
+        SortOrder::Mtime => {
+            let mut by_mtime = walls
+                .drain(..)
+                .map(|w| Ok((modified(&w)?, w)))
+                .collect::<Result<Vec<(SystemTime, String)>, Box<dyn Error>>>()?;
+            by_mtime.sort();
+            walls.extend(by_mtime.into_iter().map(|(_, w)| w));
+        }
+        SortOrder::Random => match seed {
+            // Sorted first so that the seed alone decides the order
+            Some(seed) => {
+                walls.sort();
+                walls.shuffle(&mut StdRng::seed_from_u64(seed));
+            }
+            None => walls.shuffle(&mut rand::thread_rng()),
+        },
+    }
+    Ok(())
+}
+
+/// Returns the modification time of a wallpaper
+fn modified(wall: &str) -> Result<SystemTime, Box<dyn Error>> {
+    Ok(std::fs::metadata(crate::images::file_path(wall))?.modified()?)
+}
+
+/// Compares two names, treating runs of digits as numbers and ignoring case otherwise
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        match (a.is_empty(), b.is_empty()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            _ => (),
+        }
+        let (chunk_a, rest_a) = split_chunk(a);
+        let (chunk_b, rest_b) = split_chunk(b);
+        let digits = |c: &str| c.starts_with(|c: char| c.is_ascii_digit());
+
+        let ordering = if digits(chunk_a) && digits(chunk_b) {
+            // Leading zeros don't change the value, so 01 equals 1
+            let value_a = chunk_a.trim_start_matches('0');
+            let value_b = chunk_b.trim_start_matches('0');
+            value_a
+                .len()
+                .cmp(&value_b.len())
+                .then_with(|| value_a.cmp(value_b))
+        } else {
+            chunk_a.to_lowercase().cmp(&chunk_b.to_lowercase())
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        a = rest_a;
+        b = rest_b;
+    }
+}
+
+/// Splits off the leading run of digits or of other characters
+fn split_chunk(s: &str) -> (&str, &str) {
+    let is_digit = s.starts_with(|c: char| c.is_ascii_digit());
+    let end = s
+        .find(|c: char| c.is_ascii_digit() != is_digit)
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(walls: &[&str], order: SortOrder, seed: Option<u64>) -> Vec<String> {
+        let mut walls: Vec<String> = walls.iter().map(|w| w.to_string()).collect();
+        sort_walls(&mut walls, order, seed).unwrap();
+        walls
+    }
+
+    #[test]
+    fn name_order_ignores_case() {
+        assert_eq!(sorted(&["b.png", "C.png", "a.png"], SortOrder::Name, None), ["a.png", "b.png", "C.png"]);
+    }
+
+    #[test]
+    fn random_order_is_the_same_for_a_seed() {
+        let walls = ["a.png", "b.png", "c.png", "d.png", "e.png"];
+        let first = sorted(&walls, SortOrder::Random, Some(7));
+        // The order the files were read in doesn't matter
+        let mut reversed = walls;
+        reversed.reverse();
+        assert_eq!(sorted(&reversed, SortOrder::Random, Some(7)), first);
+    }
+
+    #[test]
+    fn sort_order_from_str() {
+        assert_eq!("Natural".parse::<SortOrder>().unwrap(), SortOrder::Natural);
+        assert!("size".parse::<SortOrder>().is_err());
+    }
+
+    #[test]
+    fn natural_order_compares_numbers() {
+        assert_eq!(
+            sorted(&["img2.png", "img10.png", "img1.png"], SortOrder::Natural, None),
+            ["img1.png", "img2.png", "img10.png"]
+        );
+    }
+
+    #[test]
+    fn natural_order_keeps_zero_padded_names_in_order() {
+        assert_eq!(sorted(&["10.jpg", "02.jpg", "01.jpg"], SortOrder::Natural, None), ["01.jpg", "02.jpg", "10.jpg"]);
+        // Equal values are told apart by the raw name
+        assert_eq!(sorted(&["01.jpg", "1.jpg"], SortOrder::Natural, None), ["01.jpg", "1.jpg"]);
+    }
+}
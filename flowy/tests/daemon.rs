@@ -0,0 +1,77 @@
+// THESE TESTS RUN THE DAEMON WITH THE NULL BACKEND
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// A wallpaper the backend can't set is logged and the daemon keeps running
+#[test]
+#[cfg(unix)]
+fn daemon_keeps_running_when_a_wallpaper_cant_be_set() {
+    let home = tempfile::tempdir().unwrap();
+    let walls = home.path().join("walls");
+    std::fs::create_dir(&walls).unwrap();
+    std::fs::write(walls.join("day.png"), "").unwrap();
+    // Appending to a directory fails, so every wallpaper fails to be set
+    let log = home.path().join("log");
+    std::fs::create_dir(&log).unwrap();
+
+    let mut daemon = Command::new(env!("CARGO_BIN_EXE_flowy"))
+        .arg("--dir")
+        .arg(&walls)
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join(".config"))
+        .env("FLOWY_BACKEND", "null")
+        .env("FLOWY_BACKEND_LOG", &log)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Giving the daemon time to set the wallpaper and fail
+    thread::sleep(Duration::from_secs(2));
+    let running = daemon.try_wait().unwrap().is_none();
+    daemon.kill().unwrap();
+    let output = daemon.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(running, "the daemon stopped: {}", stderr);
+    assert!(stderr.contains("Could not set the wallpaper"), "{}", stderr);
+}
+
+/// Reloading a solar config computes the schedule again, as the times in the
+/// file are those of the day it was written
+#[test]
+#[cfg(unix)]
+fn daemon_recomputes_a_reloaded_solar_config() {
+    let home = tempfile::tempdir().unwrap();
+    let walls = home.path().join("walls");
+    std::fs::create_dir(&walls).unwrap();
+    for name in ["day.png", "night.png"] {
+        image::RgbImage::new(1, 1).save(walls.join(name)).unwrap();
+    }
+
+    let mut daemon = Command::new(env!("CARGO_BIN_EXE_flowy"))
+        .arg("--solar")
+        .arg(&walls)
+        .args(["52.5", "13.4"])
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join(".config"))
+        .env("RUST_LOG", "info")
+        .env("FLOWY_BACKEND", "null")
+        .env("FLOWY_BACKEND_LOG", home.path().join("log"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(2));
+    // Writing the config again, as an editor saving it would
+    let config = home.path().join(".config/flowy/config.toml");
+    let contents = std::fs::read(&config).unwrap();
+    std::fs::write(&config, contents).unwrap();
+    thread::sleep(Duration::from_secs(2));
+    daemon.kill().unwrap();
+    let output = daemon.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(stderr.contains("Config reloaded"), "{}", stderr);
+    assert_eq!(stderr.matches("Solar schedule updated").count(), 2, "{}", stderr);
+}
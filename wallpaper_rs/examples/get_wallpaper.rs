@@ -4,4 +4,10 @@ fn main() {
     let d = DesktopEnvt::new().expect("Desktop environment couldn't be determined");
     // This prints Ok(<file path>) on success and Err(<error message>) on failure
     println!("{:?}", d.get_wallpaper());
+    // Desktops with a wallpaper per monitor also print each of them
+    if d.capabilities().per_monitor {
+        for monitor in 0..d.monitor_count().unwrap_or(0) {
+            println!("Monitor {}: {:?}", monitor, d.get_wallpaper_for_monitor(monitor));
+        }
+    }
 }
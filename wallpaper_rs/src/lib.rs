@@ -1,10 +1,16 @@
 // THIS MODULE HANDLES THE SETTING AND GETTING
 // OF THE WALLPAPER
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use std::error::Error;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
 
 // Only one of these three sets gets compiled based on the
 // OS being run on
+mod null;
+
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "linux")]
@@ -20,27 +26,270 @@ mod windows;
 #[cfg(target_os = "windows")]
 pub use windows::DesktopEnvt;
 
+/// External tools the user would like backends to try first
+static PREFERRED_TOOLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Sets the order in which backends that can use several external tools
+/// (e.g. `feh`, `xwallpaper` and `nitrogen` on X11) try them.
+///
+/// The first installed tool from this list which the backend supports is used,
+/// falling back to the backend's built-in order. An empty list restores the default.
+pub fn set_preferred_tools(tools: &[&str]) {
+    let mut preferred = PREFERRED_TOOLS.lock().unwrap();
+    *preferred = tools.iter().map(|t| t.trim().to_string()).collect();
+}
+
+/// Returns the tools set with `set_preferred_tools`,
+/// or else those listed in the `FLOWY_TOOL` environment variable
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn preferred_tools() -> Vec<String> {
+    let preferred = PREFERRED_TOOLS.lock().unwrap().clone();
+    if !preferred.is_empty() {
+        return preferred;
+    }
+    std::env::var("FLOWY_TOOL")
+        .map(|tools| tools.split(',').map(|t| t.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Characters kept as they are in the path of a `file://` URI: letters,
+/// digits, `-._~` and the `/` between folders
+const URI_PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Turns a path, or a `file://` URI, into a `file://` URI with the path percent-encoded,
+/// e.g. `/home/me/My Wallpapers/01.jpg` into `file:///home/me/My%20Wallpapers/01.jpg`
+///
+/// ```
+/// use wallpaper_rs::to_file_uri;
+///
+/// assert_eq!(to_file_uri("/home/me/My Wallpapers/01.jpg"), "file:///home/me/My%20Wallpapers/01.jpg");
+/// assert_eq!(to_file_uri("file:///home/me/01.jpg"), "file:///home/me/01.jpg");
+/// ```
+pub fn to_file_uri(wall: &str) -> String {
+    format!("file://{}", utf8_percent_encode(&from_file_uri(wall), URI_PATH))
+}
+
+/// Turns a `file://` URI into the path it points to, decoding it.
+/// Anything else, such as a plain path, is returned as it is.
+///
+/// ```
+/// use wallpaper_rs::from_file_uri;
+///
+/// assert_eq!(from_file_uri("file:///home/me/My%20Wallpapers/01.jpg"), "/home/me/My Wallpapers/01.jpg");
+/// assert_eq!(from_file_uri("/home/me/01.jpg"), "/home/me/01.jpg");
+/// // A quoted URI, as dconf prints it, is unquoted first
+/// let quoted = enquote::enquote('"', "file:///home/me/01.jpg");
+/// assert_eq!(from_file_uri(&enquote::unquote(&quoted).unwrap()), "/home/me/01.jpg");
+/// ```
+pub fn from_file_uri(wall: &str) -> String {
+    match wall.strip_prefix("file://") {
+        Some(path) => percent_decode_str(path).decode_utf8_lossy().into_owned(),
+        None => wall.to_string(),
+    }
+}
+
+/// Features a desktop supports beyond setting one wallpaper on every screen
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Capabilities {
+    /// Each workspace (virtual desktop or activity) can have its own wallpaper
+    pub per_workspace: bool,
+    /// Each monitor can have its own wallpaper and scaling
+    pub per_monitor: bool,
+    /// `monitor_layout` notices monitors being connected or removed
+    pub hotplug: bool,
+    /// `set_wallpaper_with_transition` fades into the new wallpaper
+    pub transition: bool,
+}
+
+/// How a wallpaper is scaled and placed on the screen
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum WallpaperMode {
+    /// Scaled to cover the screen, cropping the edges
+    #[default]
+    Fill,
+    /// Scaled to fit the screen, leaving borders
+    Fit,
+    /// Shown at its original size in the middle of the screen
+    Center,
+    /// Stretched to the screen, ignoring the aspect ratio
+    Stretch,
+    /// Repeated across the screen
+    Tile,
+}
+
+impl FromStr for WallpaperMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "fill" => Ok(WallpaperMode::Fill),
+            "fit" => Ok(WallpaperMode::Fit),
+            "center" => Ok(WallpaperMode::Center),
+            "stretch" => Ok(WallpaperMode::Stretch),
+            "tile" => Ok(WallpaperMode::Tile),
+            _ => Err(format!(
+                "Unknown wallpaper mode {}, expected fill, fit, center, stretch or tile",
+                s
+            )),
+        }
+    }
+}
+
 /// A trait implemented by desktop environments. It allows setting or getting a wallpaper.
 ///
-/// On platforms where only one desktop environment exists (e.g. Windows, macOS), this can
-/// be implemented with a zero-sized type. On Linux, it is an enum.
+/// Each platform implements it with an enum of its desktop environments, which
+/// includes the null backend picked with `FLOWY_BACKEND=null`.
 pub trait Desktop: Sized {
     /// Creates a new instance of this desktop.
     ///
-    /// On Linux, this function detects the desktop environment.
-    /// It panics if the desktop environment is unsupported. It returns an error
-    /// if the desktop environment couldn't be determined (i.e., the `XDG_CURRENT_DESKTOP`
-    /// environment variable isn't set).
+    /// On Linux, this function detects the desktop environment from `XDG_CURRENT_DESKTOP`,
+    /// `DESKTOP_SESSION` or `GDMSESSION`, falling back to the X11 tools when `DISPLAY` is set.
+    /// It returns an error if the desktop environment is unsupported or couldn't be determined.
     fn new() -> Result<Self, Box<dyn Error>>;
 
     /// Sets the wallpaper for all computer screens to the specified file path.
     ///
     /// The file should be an image file supported by the patform, e.g. a JPEG.
+    /// The path should be absolute. Desktops which need a `file://` URI get one,
+    /// and a `file://` URI is taken too.
     fn set_wallpaper(&self, path: &str) -> Result<(), Box<dyn Error>>;
 
+    /// Sets the wallpaper for all computer screens, scaled and placed by `mode`.
+    ///
+    /// Desktops which can't change the scaling set the wallpaper as `set_wallpaper` does.
+    fn set_wallpaper_with_mode(&self, path: &str, mode: WallpaperMode) -> Result<(), Box<dyn Error>> {
+        let _ = mode;
+        self.set_wallpaper(path)
+    }
+
+    /// Sets the wallpaper for all computer screens, fading from the old one
+    /// over `duration`.
+    ///
+    /// Desktops which can't fade switch at once, as `set_wallpaper` does;
+    /// `capabilities` reports which ones can.
+    fn set_wallpaper_with_transition(&self, path: &str, duration: Duration) -> Result<(), Box<dyn Error>> {
+        let _ = duration;
+        self.set_wallpaper(path)
+    }
+
+    /// Sets the wallpapers shown in light and dark mode, on desktops which
+    /// keep one for each, such as GNOME.
+    ///
+    /// Other desktops show `light`.
+    fn set_wallpaper_light_dark(&self, light: &str, dark: &str) -> Result<(), Box<dyn Error>> {
+        let _ = dark;
+        self.set_wallpaper(light)
+    }
+
     /// Returns the file path to the image used as the wallpaper.
     ///
     /// If different screens have different wallpapers, only one of them is returned;
     /// the behavior depends on the platform and desktop environment.
     fn get_wallpaper(&self) -> Result<PathBuf, Box<dyn Error>>;
+
+    /// Sets the wallpaper of a single workspace, i.e. a virtual desktop or a KDE activity.
+    ///
+    /// Desktops which can't target a workspace set the wallpaper globally;
+    /// `capabilities` reports which ones can.
+    fn set_wallpaper_for_workspace(&self, workspace: &str, path: &str) -> Result<(), Box<dyn Error>> {
+        let _ = workspace;
+        self.set_wallpaper(path)
+    }
+
+    /// Returns the number of monitors wallpapers can be set on.
+    fn monitor_count(&self) -> Result<usize, Box<dyn Error>> {
+        Ok(1)
+    }
+
+    /// Returns the name of each monitor, in the order they are counted.
+    ///
+    /// Desktops which don't name their monitors return their numbers.
+    fn monitor_names(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok((0..self.monitor_count()?).map(|m| m.to_string()).collect())
+    }
+
+    /// Sets the wallpaper and its scaling for a single monitor, counting from 0.
+    ///
+    /// Desktops which can't target a monitor set the wallpaper on every screen;
+    /// `capabilities` reports which ones can.
+    fn set_wallpaper_for_monitor(
+        &self,
+        monitor: usize,
+        path: &str,
+        mode: WallpaperMode,
+    ) -> Result<(), Box<dyn Error>> {
+        let _ = (monitor, mode);
+        self.set_wallpaper(path)
+    }
+
+    /// Returns the wallpaper of a single monitor, counting from 0.
+    ///
+    /// Desktops which can't tell the monitors apart return `get_wallpaper`.
+    fn get_wallpaper_for_monitor(&self, monitor: usize) -> Result<PathBuf, Box<dyn Error>> {
+        let _ = monitor;
+        self.get_wallpaper()
+    }
+
+    /// Returns a description of the connected monitors which changes
+    /// when a monitor is connected, removed or reconfigured.
+    ///
+    /// It returns an error on desktops where this can't be observed.
+    fn monitor_layout(&self) -> Result<String, Box<dyn Error>> {
+        Err("Monitor changes can't be observed on this desktop".into())
+    }
+
+    /// Returns the features this desktop supports.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Returns the external tool used to set the wallpaper, for desktops
+    /// which can pick between several of them.
+    fn tool(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the extensions, in lowercase, of image formats this desktop is
+    /// known not to show, or to only show with extra plugins.
+    ///
+    /// Setting such a wallpaper may do nothing without reporting an error.
+    fn unsupported_formats(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_file_uri_takes_uris_and_plain_paths() {
+        assert_eq!(from_file_uri("file:///home/me/walls/01.jpg"), "/home/me/walls/01.jpg");
+        assert_eq!(from_file_uri("/home/me/walls/01.jpg"), "/home/me/walls/01.jpg");
+        // Too short for the old fixed offset
+        assert_eq!(from_file_uri("a.jpg"), "a.jpg");
+    }
+
+    #[test]
+    fn file_uris_round_trip() {
+        for path in ["/home/me/My Wallpapers/01.jpg", "/home/me/100%/#1.png", "/home/me/été.png"] {
+            let uri = to_file_uri(path);
+            assert!(!uri[7..].contains(' '), "{}", uri);
+            assert_eq!(from_file_uri(&uri), path);
+        }
+    }
+
+    #[test]
+    fn quoted_file_uris_give_the_path() {
+        // gsettings quotes with single quotes, dconf MATE keys with double quotes
+        for quoted in ["'file:///home/me/My%20Walls/01.jpg'", "\"file:///home/me/My%20Walls/01.jpg\""] {
+            let unquoted = enquote::unquote(quoted).unwrap();
+            assert_eq!(from_file_uri(&unquoted), "/home/me/My Walls/01.jpg");
+        }
+    }
 }
@@ -1,9 +1,11 @@
-use super::Desktop;
+use super::{from_file_uri, null, preferred_tools, to_file_uri, Capabilities, Desktop, WallpaperMode};
 use std::error::Error;
 use std::io::BufRead;
-use which::which;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::time::Duration;
+use which::which;
 
 /// A desktop environment
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -16,52 +18,95 @@ pub enum DesktopEnvt {
     KDE,
     BSPWM,
     I3,
+    /// Uses the X11 tools, like BSPWM and i3
+    Qtile,
+    /// Uses the X11 tools, like BSPWM and i3
+    LeftWM,
+    /// Uses the Wayland tools
+    River,
+    /// Uses the Wayland tools, preferring swaybg
+    Sway,
+    /// Uses hyprpaper through hyprctl
+    Hyprland,
+    /// Any other X11 window manager, using the X11 tools.
+    /// Selected when the desktop is unknown but `DISPLAY` is set.
+    GenericX11,
+    /// Records wallpapers instead of setting them, for headless testing.
+    /// Selected with `FLOWY_BACKEND=null`.
+    Null,
 }
 
+/// The last wallpaper set with an X11 tool, for the tools which can't be asked
+static X11_WALLPAPER: Mutex<Option<String>> = Mutex::new(None);
+
+/// The swaybg process drawing the wallpaper, and the wallpaper it draws
+static SWAYBG: Mutex<Option<(Child, String)>> = Mutex::new(None);
+
 impl Desktop for DesktopEnvt {
     fn new() -> Result<Self, Box<dyn Error>> {
-        let desktop = std::env::var("XDG_CURRENT_DESKTOP")?;
-        if is_gnome_compliant(&desktop) {
-            Ok(DesktopEnvt::GNOME)
-        } else {
-            Ok(match &desktop[..] {
-                "X-Cinnamon" => DesktopEnvt::Cinnamon,
-                "MATE" => DesktopEnvt::MATE,
-                "XFCE" => DesktopEnvt::XFCE,
-                "Deepin" => DesktopEnvt::Deepin,
-                "KDE" => DesktopEnvt::KDE,
-                "bspwm" => DesktopEnvt::BSPWM,
-                "i3" => DesktopEnvt::I3,
-                _ => panic!("Unsupported Desktop Environment"),
-            })
+        if null::selected() {
+            return Ok(DesktopEnvt::Null);
+        }
+        // Login managers set different variables, so the first one naming a known desktop wins
+        let mut names = Vec::new();
+        for var in DESKTOP_VARS {
+            // Some display managers set them to an empty string, which counts as unset
+            if let Some(name) = std::env::var(var).ok().filter(|n| !n.trim().is_empty()) {
+                if let Some(desktop) = desktop_from_name(&name) {
+                    return Ok(desktop);
+                }
+                names.push(name);
+            }
+        }
+        // Sway and Hyprland do not always set XDG_CURRENT_DESKTOP
+        if std::env::var_os("SWAYSOCK").is_some() {
+            return Ok(DesktopEnvt::Sway);
+        }
+        if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            return Ok(DesktopEnvt::Hyprland);
+        }
+        if std::env::var_os("DISPLAY").is_some() {
+            return Ok(DesktopEnvt::GenericX11);
+        }
+        match names.first() {
+            Some(name) => Err(format!("unsupported desktop environment: {}", name).into()),
+            None if std::env::var_os("WAYLAND_DISPLAY").is_some() => Err(
+                "Could not tell which Wayland compositor is running. Set XDG_CURRENT_DESKTOP, e.g. to sway".into(),
+            ),
+            None => Err(format!(
+                "None of {} is set. Set XDG_CURRENT_DESKTOP to your desktop, e.g. GNOME",
+                DESKTOP_VARS.join(", ")
+            )
+            .into()),
         }
     }
 
     fn set_wallpaper(&self, path: &str) -> Result<(), Box<dyn Error>> {
-        let path = enquote::enquote('"', &format!("{}", path));
-
         match self {
+            // Only picture-uri-dark is shown while the dark style is on
             DesktopEnvt::GNOME => {
-                Command::new("gsettings")
-                    .args(&["set", "org.gnome.desktop.background", "picture-uri", &path])
-                    .output()?;
+                self.set_wallpaper_light_dark(path, path)?;
             }
 
             DesktopEnvt::Cinnamon => {
+                let uri = enquote::enquote('"', &to_file_uri(path));
                 Command::new("dconf")
-                    .args(&[
+                    .args([
                         "write",
                         "/org/cinnamon/desktop/background/picture-uri",
-                        &path,
+                        &uri,
                     ])
                     .output()?;
             }
 
             DesktopEnvt::MATE => {
-                let mate_path = path.replace("file://", "");
+                // dconf takes a quoted string, without the file:// an
+                // older config may have
+                let mate_path = from_file_uri(path);
+                let mate_path = enquote::enquote('"', &mate_path);
 
                 Command::new("dconf")
-                    .args(&[
+                    .args([
                         "write",
                         "/org/mate/desktop/background/picture-filename",
                         &mate_path,
@@ -70,45 +115,22 @@ impl Desktop for DesktopEnvt {
             }
 
             DesktopEnvt::XFCE => {
-                let path_unquoted = enquote::unquote(&path).unwrap();
-                let xfce_path = path_unquoted
-                    .strip_prefix("file://")
-                    .unwrap();
-                
-                // Get the raw output of xfconf-query for the wallpaper
-                let values_raw = Command::new("xfconf-query")
-                    .args(&[
-                        "-c",
-                        "xfce4-desktop",
-                        "-p",
-                        "/backdrop/screen0",
-                        "-lv",
-                    ])
-                    .output()
-                    .unwrap()
-                    .stdout;
-
-                // Filter out unwanted values (everything except */last-image)
-                let values_str = match std::str::from_utf8(&values_raw) {
-                    Ok(v) => v.to_string(),
-                    Err(_) => "/backdrop/screen0/monitor0/workspace0/last-image".to_string(),
-                };
+                let xfce_path = from_file_uri(path);
 
-                // Collect the keys for the filtered values
-                let values_vec: Vec<&str> = values_str
-                    .split_whitespace()
-                    .step_by(2)
+                // Collect the keys for the wallpaper of each monitor and workspace
+                let values_vec: Vec<String> = xfce_keys()
+                    .into_iter()
                     .filter(|v| v.contains("last-image"))
                     .collect();
 
                 // Set all the keys to the new wallpaper
                 for v in values_vec {
                     Command::new("xfconf-query")
-                        .args(&[
+                        .args([
                             "-c",
                             "xfce4-desktop",
                             "-p",
-                            v,
+                            &v,
                             "-s",
                             &xfce_path,
                         ])
@@ -117,56 +139,44 @@ impl Desktop for DesktopEnvt {
             }
 
             DesktopEnvt::Deepin => {
+                let uri = enquote::enquote('"', &to_file_uri(path));
                 Command::new("dconf")
-                    .args(&[
+                    .args([
                         "write",
                         "/com/deepin/wrap/gnome/desktop/background/picture-uri",
-                        &path,
+                        &uri,
                     ])
                     .output()?;
             }
 
             DesktopEnvt::KDE => {
                 // KDE needs plasma shell scripting to change the wallpaper
-                let kde_set_arg = format!(
-                    r#"
-                    const monitors = desktops()
-                    for (var i = 0; i < monitors.length; i++) {{
-                        monitors[i].wallpaperPlugin = "org.kde.image"
-                        monitors[i].currentConfigGroup = ["Wallpaper"]
-                        monitors[i].writeConfig("Image", {})
-                    }}"#,
-                    &path
-                );
-
-                let which_qdbus = which("qdbus");
-                
-                if which_qdbus.is_ok() {
-                    Command::new("qdbus")
-                        .args(&[
-                            "org.kde.plasmashell",
-                            "/PlasmaShell",
-                            "org.kde.PlasmaShell.evaluateScript",
-                            &kde_set_arg,
-                        ])
-                        .output()?;
-                } else {
-                    Command::new("qdbus-qt5")
-                    .args(&[
-                        "org.kde.plasmashell",
-                        "/PlasmaShell",
-                        "org.kde.PlasmaShell.evaluateScript",
-                        &kde_set_arg,
-                    ])
-                    .output()?;
-                }
+                let uri = enquote::enquote('"', &to_file_uri(path));
+                kde_set_wallpaper("desktops()", &uri)?;
             }
 
-            DesktopEnvt::BSPWM | DesktopEnvt::I3 => {
-                Command::new("feh")
-                    .args(&["--bg-fill", &path.replace("\"", "")])
-                    .output()?;
+            DesktopEnvt::BSPWM
+            | DesktopEnvt::I3
+            | DesktopEnvt::Qtile
+            | DesktopEnvt::LeftWM
+            | DesktopEnvt::GenericX11
+            | DesktopEnvt::River
+            | DesktopEnvt::Sway => {
+                // These tools always scale the wallpaper, so fill is the default
+                self.set_wallpaper_with_mode(path, WallpaperMode::Fill)?;
+            }
+
+            DesktopEnvt::Hyprland => {
+                let path = from_file_uri(path);
+                hyprpaper(&["preload", &path])?;
+                // An empty monitor sets the wallpaper on all of them
+                hyprpaper(&["wallpaper", &format!(",{}", path)])?;
+                // Images which are no longer shown are kept in memory otherwise
+                hyprpaper(&["unload", "unused"])?;
             }
+
+            // Written to the file in FLOWY_BACKEND_LOG, or stdout
+            DesktopEnvt::Null => null::set_wallpaper(path)?,
         }
 
         Ok(())
@@ -174,8 +184,10 @@ impl Desktop for DesktopEnvt {
 
     fn get_wallpaper(&self) -> Result<PathBuf, Box<dyn Error>> {
         let output = match self {
+            // The light mode wallpaper, which is what flowy sets
+            // for both modes unless it is given a dark one
             DesktopEnvt::GNOME => Command::new("gsettings")
-                .args(&["get", "org.gnome.desktop.background", "picture-uri"])
+                .args(["get", "org.gnome.desktop.background", "picture-uri"])
                 .output()?,
 
             DesktopEnvt::Cinnamon => Command::new("dconf")
@@ -184,11 +196,11 @@ impl Desktop for DesktopEnvt {
                 .output()?,
 
             DesktopEnvt::MATE => Command::new("dconf")
-                .args(&["read", "/org/mate/desktop/background/picture-filename"])
+                .args(["read", "/org/mate/desktop/background/picture-filename"])
                 .output()?,
 
             DesktopEnvt::XFCE => Command::new("xfconf-query")
-                .args(&[
+                .args([
                     "-c",
                     "xfce4-desktop",
                     "-p",
@@ -197,29 +209,621 @@ impl Desktop for DesktopEnvt {
                 .output()?,
 
             DesktopEnvt::Deepin => Command::new("dconf")
-                .args(&[
+                .args([
                     "read",
                     "/com/deepin/wrap/gnome/desktop/background/picture-uri",
                 ])
                 .output()?,
-            DesktopEnvt::KDE => return Ok(kde_get_wallpaper()?),
-            DesktopEnvt::BSPWM | DesktopEnvt::I3 => Command::new("sed")
-                .args(&[
-                    "-n",
-                    "'s/feh.*\\('.*'\\)/\\1/gp'",
-                    &format!("/home/{}/.fehbg", std::env::var("USER")?.trim()),
-                ])
-                .output()?,
+            DesktopEnvt::KDE => return kde_get_wallpaper(),
+            DesktopEnvt::Null => return null::get_wallpaper(),
+            DesktopEnvt::River | DesktopEnvt::Sway => {
+                if let Some((_, path)) = &*SWAYBG.lock().unwrap() {
+                    return Ok(PathBuf::from(path));
+                }
+                return swww_get_wallpaper();
+            }
+            DesktopEnvt::Hyprland => {
+                // Lines look like `DP-1 = /path/to/wallpaper.png`
+                return hyprpaper(&["listactive"])?
+                    .lines()
+                    .find_map(|line| line.split(" = ").nth(1))
+                    .map(|path| PathBuf::from(path.trim()))
+                    .ok_or_else(|| "hyprpaper is not showing a wallpaper".into());
+            }
+            DesktopEnvt::BSPWM
+            | DesktopEnvt::I3
+            | DesktopEnvt::Qtile
+            | DesktopEnvt::LeftWM
+            | DesktopEnvt::GenericX11 => return x11_get_wallpaper(self.tool()),
         };
 
-        let output = enquote::unquote(String::from_utf8(output.stdout)?.trim().into())?;
-        Ok(PathBuf::from(output))
+        // GNOME, Cinnamon and Deepin answer with a URI
+        let output = enquote::unquote(String::from_utf8(output.stdout)?.trim())?;
+        Ok(PathBuf::from(from_file_uri(&output)))
     }
+
+    fn set_wallpaper_with_mode(&self, path: &str, mode: WallpaperMode) -> Result<(), Box<dyn Error>> {
+        match self {
+            DesktopEnvt::GNOME | DesktopEnvt::Cinnamon | DesktopEnvt::MATE => {
+                self.set_wallpaper(path)?;
+                let options = match mode {
+                    WallpaperMode::Fill => "zoom",
+                    WallpaperMode::Fit => "scaled",
+                    WallpaperMode::Center => "centered",
+                    WallpaperMode::Stretch => "stretched",
+                    WallpaperMode::Tile => "wallpaper",
+                };
+                if *self == DesktopEnvt::GNOME {
+                    Command::new("gsettings")
+                        .args(["set", "org.gnome.desktop.background", "picture-options", options])
+                        .output()?;
+                } else {
+                    let desktop = if *self == DesktopEnvt::Cinnamon { "cinnamon" } else { "mate" };
+                    Command::new("dconf")
+                        .arg("write")
+                        .arg(format!("/org/{}/desktop/background/picture-options", desktop))
+                        .arg(format!("'{}'", options))
+                        .output()?;
+                }
+            }
+
+            DesktopEnvt::KDE | DesktopEnvt::XFCE => {
+                for monitor in 0..self.monitor_count()? {
+                    self.set_wallpaper_for_monitor(monitor, path, mode)?;
+                }
+            }
+
+            DesktopEnvt::BSPWM
+            | DesktopEnvt::I3
+            | DesktopEnvt::Qtile
+            | DesktopEnvt::LeftWM
+            | DesktopEnvt::GenericX11 => {
+                let tool = self.tool().ok_or_else(|| {
+                    format!("None of {} is installed", X11_TOOLS.join(", "))
+                })?;
+                let args: &[&str] = match (&tool[..], mode) {
+                    ("xwallpaper", WallpaperMode::Fill) => &["--zoom"],
+                    ("xwallpaper", WallpaperMode::Fit) => &["--maximize"],
+                    ("xwallpaper", WallpaperMode::Center) => &["--center"],
+                    ("xwallpaper", WallpaperMode::Stretch) => &["--stretch"],
+                    ("xwallpaper", WallpaperMode::Tile) => &["--tile"],
+                    ("nitrogen", WallpaperMode::Fill) => &["--set-zoom-fill", "--save"],
+                    ("nitrogen", WallpaperMode::Fit) => &["--set-zoom", "--save"],
+                    ("nitrogen", WallpaperMode::Center) => &["--set-centered", "--save"],
+                    ("nitrogen", WallpaperMode::Stretch) => &["--set-scaled", "--save"],
+                    ("nitrogen", WallpaperMode::Tile) => &["--set-tiled", "--save"],
+                    (_, WallpaperMode::Fill) => &["--bg-fill"],
+                    (_, WallpaperMode::Fit) => &["--bg-max"],
+                    (_, WallpaperMode::Center) => &["--bg-center"],
+                    (_, WallpaperMode::Stretch) => &["--bg-scale"],
+                    (_, WallpaperMode::Tile) => &["--bg-tile"],
+                };
+                let path = from_file_uri(path);
+                Command::new(&tool).args(args).arg(&path).output()?;
+                *X11_WALLPAPER.lock().unwrap() = Some(path);
+            }
+
+            DesktopEnvt::River | DesktopEnvt::Sway => {
+                let tools = if *self == DesktopEnvt::Sway { SWAY_TOOLS } else { WAYLAND_TOOLS };
+                let tool = self.tool().ok_or_else(|| {
+                    format!("None of {} is installed", tools.join(", "))
+                })?;
+                let path = &from_file_uri(path);
+                if tool == "swww" {
+                    // swww can only crop, fit or keep the original size
+                    let resize = match mode {
+                        WallpaperMode::Fit => "fit",
+                        WallpaperMode::Center => "no",
+                        _ => "crop",
+                    };
+                    swww_set_wallpaper(path, resize, None)?;
+                } else {
+                    swaybg_set_wallpaper(path, mode)?;
+                }
+            }
+
+            _ => self.set_wallpaper(path)?,
+        }
+        Ok(())
+    }
+
+    fn set_wallpaper_with_transition(&self, path: &str, duration: Duration) -> Result<(), Box<dyn Error>> {
+        if !self.capabilities().transition {
+            return self.set_wallpaper(path);
+        }
+        swww_set_wallpaper(&from_file_uri(path), "crop", Some(duration))
+    }
+
+    fn set_wallpaper_light_dark(&self, light: &str, dark: &str) -> Result<(), Box<dyn Error>> {
+        if *self != DesktopEnvt::GNOME {
+            return self.set_wallpaper(light);
+        }
+        for (key, path) in [("picture-uri", light), ("picture-uri-dark", dark)] {
+            let path = enquote::enquote('"', &to_file_uri(path));
+            // Versions of GNOME before 42 have no picture-uri-dark key
+            Command::new("gsettings")
+                .args(["set", "org.gnome.desktop.background", key, &path])
+                .output()?;
+        }
+        Ok(())
+    }
+
+    fn set_wallpaper_for_workspace(&self, workspace: &str, path: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            // Workspaces are activities on KDE
+            DesktopEnvt::KDE => {
+                let uri = enquote::enquote('"', &to_file_uri(path));
+                let activity = enquote::enquote('"', workspace);
+                kde_set_wallpaper(&format!("desktopsForActivity({})", activity), &uri)
+            }
+
+            _ => self.set_wallpaper(path),
+        }
+    }
+
+    fn monitor_count(&self) -> Result<usize, Box<dyn Error>> {
+        match self {
+            DesktopEnvt::KDE => Ok(kde_eval("print(desktops().length)")?.trim().parse()?),
+            DesktopEnvt::XFCE => Ok(xfce_monitors().len().max(1)),
+            _ => Ok(1),
+        }
+    }
+
+    fn monitor_names(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        match self {
+            // The key prefixes look like /backdrop/screen0/monitorHDMI-1/
+            DesktopEnvt::XFCE => Ok(xfce_monitors()
+                .iter()
+                .map(|m| m.trim_end_matches('/').trim_start_matches("/backdrop/screen0/monitor").to_string())
+                .collect()),
+            _ => Ok((0..self.monitor_count()?).map(|m| m.to_string()).collect()),
+        }
+    }
+
+    fn set_wallpaper_for_monitor(
+        &self,
+        monitor: usize,
+        path: &str,
+        mode: WallpaperMode,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            DesktopEnvt::KDE => {
+                let fill_mode = match mode {
+                    WallpaperMode::Stretch => 0,
+                    WallpaperMode::Fit => 1,
+                    WallpaperMode::Fill => 2,
+                    WallpaperMode::Tile => 3,
+                    WallpaperMode::Center => 6,
+                };
+                kde_eval(&format!(
+                    r#"
+                    const monitor = desktops()[{}]
+                    monitor.wallpaperPlugin = "org.kde.image"
+                    monitor.currentConfigGroup = ["Wallpaper"]
+                    monitor.writeConfig("Image", {})
+                    monitor.writeConfig("FillMode", {})"#,
+                    monitor,
+                    enquote::enquote('"', &to_file_uri(path)),
+                    fill_mode
+                ))?;
+                Ok(())
+            }
+
+            DesktopEnvt::XFCE => {
+                let monitors = xfce_monitors();
+                let prefix = monitors
+                    .get(monitor)
+                    .ok_or_else(|| format!("No monitor {}", monitor))?;
+                let image_style = match mode {
+                    WallpaperMode::Center => "1",
+                    WallpaperMode::Tile => "2",
+                    WallpaperMode::Stretch => "3",
+                    WallpaperMode::Fit => "4",
+                    WallpaperMode::Fill => "5",
+                };
+                let xfce_path = &from_file_uri(path);
+                for key in xfce_keys().iter().filter(|k| k.starts_with(prefix)) {
+                    let value = if key.ends_with("/last-image") {
+                        xfce_path
+                    } else if key.ends_with("/image-style") {
+                        image_style
+                    } else {
+                        continue;
+                    };
+                    Command::new("xfconf-query")
+                        .args(["-c", "xfce4-desktop", "-p", key, "-s", value])
+                        .output()?;
+                }
+                Ok(())
+            }
+
+            _ => self.set_wallpaper(path),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            per_workspace: *self == DesktopEnvt::KDE,
+            per_monitor: matches!(self, DesktopEnvt::KDE | DesktopEnvt::XFCE),
+            hotplug: is_x11_session(self) && which("xrandr").is_ok(),
+            // Only swww draws a fade, the other tools replace the image at once
+            transition: matches!(self, DesktopEnvt::River | DesktopEnvt::Sway)
+                && self.tool().as_deref() == Some("swww"),
+        }
+    }
+
+    fn monitor_layout(&self) -> Result<String, Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return Err("The null backend has no monitors".into());
+        }
+        // Under Wayland xrandr only sees the monitors XWayland was told about
+        if !is_x11_session(self) {
+            return Err("Monitor changes can only be observed in an X11 session".into());
+        }
+        let output = Command::new("xrandr").arg("--listmonitors").output()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn tool(&self) -> Option<String> {
+        match self {
+            DesktopEnvt::BSPWM
+            | DesktopEnvt::I3
+            | DesktopEnvt::Qtile
+            | DesktopEnvt::LeftWM
+            | DesktopEnvt::GenericX11 => {
+                pick_tool(X11_TOOLS)
+            }
+            DesktopEnvt::River => pick_tool(WAYLAND_TOOLS),
+            DesktopEnvt::Sway => pick_tool(SWAY_TOOLS),
+            _ => None,
+        }
+    }
+
+    fn unsupported_formats(&self) -> &'static [&'static str] {
+        match (self, self.tool().as_deref()) {
+            // gdk-pixbuf needs extra loaders for AVIF and HEIF
+            (
+                DesktopEnvt::GNOME | DesktopEnvt::Cinnamon | DesktopEnvt::MATE | DesktopEnvt::XFCE | DesktopEnvt::Deepin,
+                _,
+            ) => &["avif", "heic", "heif"],
+            (DesktopEnvt::KDE | DesktopEnvt::Null, _) => &[],
+            (DesktopEnvt::Hyprland, _) => &["avif", "heic", "heif", "bmp", "tif", "tiff"],
+            (_, Some("feh")) => &["avif", "heic", "heif"],
+            (_, Some("swww")) => &["heic", "heif"],
+            // xwallpaper, nitrogen and swaybg
+            _ => &["webp", "avif", "heic", "heif"],
+        }
+    }
+}
+
+/// Sets the wallpaper of the plasma desktops returned by `desktops`,
+/// a plasma script expression such as `desktops()`.
+/// `path` must already be quoted.
+fn kde_set_wallpaper(desktops: &str, path: &str) -> Result<(), Box<dyn Error>> {
+    kde_eval(&format!(
+        r#"
+        const monitors = {}
+        for (var i = 0; i < monitors.length; i++) {{
+            monitors[i].wallpaperPlugin = "org.kde.image"
+            monitors[i].currentConfigGroup = ["Wallpaper"]
+            monitors[i].writeConfig("Image", {})
+        }}"#,
+        desktops, path
+    ))?;
+
+    Ok(())
+}
+
+/// Names of the qdbus binary on different distributions
+const QDBUS_COMMANDS: &[&str] = &["qdbus", "qdbus6", "qdbus-qt5"];
+
+/// Runs a plasma shell script and returns what it printed.
+///
+/// It uses the first installed qdbus binary, falling back to `dbus-send`.
+fn kde_eval(script: &str) -> Result<String, Box<dyn Error>> {
+    // Plasma may not be ready to run scripts right after login
+    let mut attempt = 1;
+    loop {
+        match kde_eval_once(script) {
+            Err(e) if attempt < KDE_ATTEMPTS && !e.to_string().starts_with(KDE_MISSING_TOOLS) => {
+                log::debug!("Plasma script failed, attempt {}: {}", attempt, e);
+                attempt += 1;
+                std::thread::sleep(KDE_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// How often a plasma script is tried before giving up
+const KDE_ATTEMPTS: u32 = 3;
+/// Time between attempts to run a plasma script
+const KDE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+/// Start of the error when neither qdbus nor dbus-send is installed
+const KDE_MISSING_TOOLS: &str = "Setting the wallpaper on KDE needs";
+
+/// Runs a plasma script once, failing if plasma reports an error
+fn kde_eval_once(script: &str) -> Result<String, Box<dyn Error>> {
+    let (output, printed) = if let Some(qdbus) = QDBUS_COMMANDS.iter().find(|q| which(q).is_ok()) {
+        let output = Command::new(qdbus)
+            .args([
+                "org.kde.plasmashell",
+                "/PlasmaShell",
+                "org.kde.PlasmaShell.evaluateScript",
+                script,
+            ])
+            .output()?;
+        let printed = String::from_utf8(output.stdout.clone())?;
+        (output, printed)
+    } else if which("dbus-send").is_ok() {
+        let output = Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.kde.plasmashell",
+                "/PlasmaShell",
+                "org.kde.PlasmaShell.evaluateScript",
+                &format!("string:{}", script),
+            ])
+            .output()?;
+        // The reply looks like: method return ... \n   string "<printed text>"
+        let reply = String::from_utf8(output.stdout.clone())?;
+        let printed = match (reply.find("string \""), reply.rfind('"')) {
+            (Some(start), Some(end)) if start + 8 <= end => reply[start + 8..end].to_string(),
+            _ => String::new(),
+        };
+        (output, printed)
+    } else {
+        return Err(format!(
+            "{} one of {} or dbus-send",
+            KDE_MISSING_TOOLS,
+            QDBUS_COMMANDS.join(", ")
+        )
+        .into());
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    log::debug!("Plasma script replied: {:?} {:?}", printed, stderr.trim());
+
+    if !output.status.success() {
+        return Err(format!("Plasma script failed: {}", stderr.trim()).into());
+    }
+    // Errors thrown by the script are printed rather than failing the call
+    if printed.contains("Error:") {
+        return Err(format!("Plasma script failed: {}", printed.trim()).into());
+    }
+    // Scripts which print something answer with nothing when plasma isn't ready
+    if script.contains("print(") && printed.trim().is_empty() {
+        return Err("Plasma script returned nothing".into());
+    }
+    Ok(printed)
+}
+
+/// Returns the xfconf keys of the XFCE desktop backdrop
+fn xfce_keys() -> Vec<String> {
+    // Get the raw output of xfconf-query for the wallpaper
+    let values_raw = Command::new("xfconf-query")
+        .args(["-c", "xfce4-desktop", "-p", "/backdrop/screen0", "-l"])
+        .output()
+        .map(|o| o.stdout)
+        .unwrap_or_default();
+
+    match String::from_utf8(values_raw) {
+        Ok(v) if !v.trim().is_empty() => v.lines().map(|l| l.trim().to_string()).collect(),
+        _ => vec!["/backdrop/screen0/monitor0/workspace0/last-image".to_string()],
+    }
+}
+
+/// Returns the xfconf key prefix of each XFCE monitor, e.g. `/backdrop/screen0/monitorHDMI-1/`
+fn xfce_monitors() -> Vec<String> {
+    let mut monitors: Vec<String> = xfce_keys()
+        .iter()
+        .filter_map(|k| {
+            let monitor = k.split('/').nth(3)?;
+            if monitor.starts_with("monitor") {
+                Some(format!("/backdrop/screen0/{}/", monitor))
+            } else {
+                None
+            }
+        })
+        .collect();
+    monitors.sort();
+    monitors.dedup();
+    monitors
+}
+
+/// Tools which can set the wallpaper on X11 window managers, in default order
+const X11_TOOLS: &[&str] = &["feh", "xwallpaper", "nitrogen"];
+
+/// Tools which can set the wallpaper on Wayland compositors, in default order
+const WAYLAND_TOOLS: &[&str] = &["swww", "swaybg"];
+
+/// The Wayland tools in the order preferred on Sway
+const SWAY_TOOLS: &[&str] = &["swaybg", "swww"];
+
+/// Returns the first installed tool out of `supported`, trying the
+/// user's preferred tools before the built-in order
+fn pick_tool(supported: &[&str]) -> Option<String> {
+    let preferred = preferred_tools();
+    preferred
+        .iter()
+        .map(|t| &t[..])
+        .filter(|t| supported.contains(t))
+        .chain(supported.iter().copied())
+        .find(|t| which(t).is_ok())
+        .map(|t| t.to_string())
+}
+
+/// Starts swaybg drawing `path` scaled by `mode`, then stops the swaybg it replaces.
+///
+/// swaybg keeps running for as long as the wallpaper is shown. Instances
+/// flowy did not start, e.g. from the sway config, are stopped first.
+fn swaybg_set_wallpaper(path: &str, mode: WallpaperMode) -> Result<(), Box<dyn Error>> {
+    let mut swaybg = SWAYBG.lock().unwrap();
+    if swaybg.is_none() {
+        Command::new("pkill").arg("-x").arg("swaybg").output()?;
+    }
+    let mode = match mode {
+        WallpaperMode::Fill => "fill",
+        WallpaperMode::Fit => "fit",
+        WallpaperMode::Center => "center",
+        WallpaperMode::Stretch => "stretch",
+        WallpaperMode::Tile => "tile",
+    };
+    let child = Command::new("swaybg").args(["-m", mode, "-i", path]).spawn()?;
+    // The old instance goes once the new one is running, to avoid a gap
+    if let Some((mut old, _)) = swaybg.replace((child, path.to_string())) {
+        // It may have exited on its own already
+        let _ = old.kill();
+        old.wait()?;
+    }
+    Ok(())
+}
+
+/// Sends a request to hyprpaper and returns its reply
+fn hyprpaper(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    if which("hyprctl").is_err() {
+        return Err("Setting the wallpaper on Hyprland needs hyprctl and hyprpaper".into());
+    }
+    let output = Command::new("hyprctl").arg("hyprpaper").args(args).output()?;
+    let reply = String::from_utf8(output.stdout)?;
+    // hyprctl reports a missing hyprpaper socket on stdout
+    if !output.status.success() || reply.contains("Couldn't connect") || reply.starts_with("error") {
+        return Err(format!(
+            "hyprpaper did not respond ({}). Is it running? Add `exec-once = hyprpaper` to hyprland.conf",
+            reply.trim()
+        )
+        .into());
+    }
+    Ok(reply)
+}
+
+/// Shows `path` with swww, resized by `resize` (crop, fit or no),
+/// fading in over `transition` or else with swww's default transition
+fn swww_set_wallpaper(path: &str, resize: &str, transition: Option<Duration>) -> Result<(), Box<dyn Error>> {
+    let mut command = Command::new("swww");
+    command.args(["img", "--resize", resize]);
+    if let Some(duration) = transition {
+        command
+            .args(["--transition-type", "fade", "--transition-duration"])
+            .arg(format!("{:.2}", duration.as_secs_f64()));
+    }
+    command.arg(path).output()?;
+    Ok(())
+}
+
+/// Returns the wallpaper shown by swww on the first output.
+///
+/// `swww query` prints lines like
+/// "eDP-1: 1920x1080, scale: 1, currently displaying: image: /path"
+fn swww_get_wallpaper() -> Result<PathBuf, Box<dyn Error>> {
+    let output = Command::new("swww").arg("query").output()?;
+    let output = String::from_utf8(output.stdout)?;
+    output
+        .lines()
+        .find_map(|line| line.split("image: ").nth(1))
+        .map(|path| PathBuf::from(path.trim()))
+        .ok_or_else(|| "Could not get the wallpaper from swww".into())
+}
+
+/// Environment variables naming the desktop, in the order they are trusted
+const DESKTOP_VARS: &[&str] = &["XDG_CURRENT_DESKTOP", "DESKTOP_SESSION", "GDMSESSION"];
+
+/// Returns the wallpaper of an X11 window manager. feh records it in `~/.fehbg`,
+/// the other tools can't be asked, so only a wallpaper set by this process is known.
+fn x11_get_wallpaper(tool: Option<String>) -> Result<PathBuf, Box<dyn Error>> {
+    if tool.as_deref() == Some("feh") {
+        let fehbg = dirs_next::home_dir().ok_or("Could not determine the home directory")?.join(".fehbg");
+        if let Some(path) = std::fs::read_to_string(&fehbg).ok().as_deref().and_then(parse_fehbg) {
+            return Ok(path);
+        }
+    }
+    if let Some(path) = &*X11_WALLPAPER.lock().unwrap() {
+        return Ok(PathBuf::from(path));
+    }
+    match tool {
+        Some(tool) => Err(format!("The wallpaper can't be read with {}", tool).into()),
+        None => Err(format!("None of {} is installed", X11_TOOLS.join(", ")).into()),
+    }
+}
+
+/// Returns the first wallpaper of a `.fehbg` script, whose feh line looks like
+/// `feh --no-fehbg --bg-fill '/home/me/it'\''s.jpg'`
+fn parse_fehbg(script: &str) -> Option<PathBuf> {
+    let line = script.lines().find(|line| line.trim_start().starts_with("feh "))?;
+    shell_words(line)
+        .into_iter()
+        .skip(1)
+        .find(|word| !word.starts_with('-'))
+        .map(PathBuf::from)
+}
+
+/// Splits a shell command into words, following single quotes and backslashes
+fn shell_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Whether the desktop runs directly on X11, where xrandr sees every monitor
+fn is_x11_session(desktop: &DesktopEnvt) -> bool {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|kind| kind == "wayland");
+    !wayland
+        && !matches!(
+            desktop,
+            DesktopEnvt::River | DesktopEnvt::Sway | DesktopEnvt::Hyprland | DesktopEnvt::Null
+        )
 }
 
-/// Check if desktop is Gnome compliant
+/// Returns the desktop a name like `KDE` or `plasma` stands for, ignoring case.
+/// Names may list several desktops separated by colons, e.g. `ubuntu:GNOME`,
+/// of which the first known one is used.
+fn desktop_from_name(name: &str) -> Option<DesktopEnvt> {
+    name.split(':').find_map(desktop_from_token)
+}
+
+/// Returns the desktop a single name stands for, ignoring case
+fn desktop_from_token(name: &str) -> Option<DesktopEnvt> {
+    let name = name.trim().to_lowercase();
+    if is_gnome_compliant(&name) {
+        return Some(DesktopEnvt::GNOME);
+    }
+    Some(match &name[..] {
+        "x-cinnamon" | "cinnamon" => DesktopEnvt::Cinnamon,
+        "mate" => DesktopEnvt::MATE,
+        "xfce" | "xfce4" => DesktopEnvt::XFCE,
+        "deepin" => DesktopEnvt::Deepin,
+        "kde" | "plasma" | "plasmawayland" => DesktopEnvt::KDE,
+        "bspwm" => DesktopEnvt::BSPWM,
+        "i3" => DesktopEnvt::I3,
+        "qtile" => DesktopEnvt::Qtile,
+        "leftwm" => DesktopEnvt::LeftWM,
+        "river" => DesktopEnvt::River,
+        "sway" => DesktopEnvt::Sway,
+        "hyprland" => DesktopEnvt::Hyprland,
+        _ => return None,
+    })
+}
+
+/// Check if desktop is Gnome compliant, given its lowercase name
 fn is_gnome_compliant(desktop: &str) -> bool {
-    desktop.contains("GNOME") || desktop == "Unity" || desktop == "Pantheon"
+    desktop.contains("gnome") || desktop == "unity" || desktop == "pantheon" || desktop == "ubuntu"
 }
 
 /// Returns the absolute wallpaper path on KDE, if possible.
@@ -230,20 +834,86 @@ fn kde_get_wallpaper() -> Result<PathBuf, Box<dyn Error>> {
     let mut path = dirs_next::config_dir().ok_or("Could not determine config directory")?;
     path.push("plasma-org.kde.plasma.desktop-appletsrc");
 
+    // Plasma only writes the file once the desktop has been configured,
+    // so the running shell is asked instead
+    if !path.exists() {
+        log::debug!("{} does not exist, asking plasma", path.display());
+        let image = kde_eval(
+            r#"
+            const monitor = desktops()[0]
+            monitor.currentConfigGroup = ["Wallpaper", "org.kde.image", "General"]
+            print(monitor.readConfig("Image"))"#,
+        )
+        .map_err(|e| format!("{} does not exist and plasma did not answer: {}", path.display(), e))?;
+        let image = image.trim();
+        return Ok(PathBuf::from(from_file_uri(image)));
+    }
+
     // Opening the file into a buffer reader
     let file = std::fs::File::open(path)?;
 
     let reader = std::io::BufReader::new(file);
     for line in reader.lines() {
         let line = line?;
-        if line.starts_with("Image=") {
-            let mut line = line[6..].trim();
-            if line.starts_with("file://") {
-                line = &line[7..];
-            }
-            return Ok(PathBuf::from(line));
+        if let Some(image) = line.strip_prefix("Image=") {
+            let image = image.trim();
+            return Ok(PathBuf::from(from_file_uri(image)));
         }
     }
 
     Err("KDE Image not found".into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fehbg_reads_the_first_wallpaper() {
+        let script = "#!/bin/sh\nfeh --no-fehbg --bg-fill '/home/me/My Wallpapers/01.jpg' '/home/me/02.jpg' \n";
+        assert_eq!(parse_fehbg(script), Some(PathBuf::from("/home/me/My Wallpapers/01.jpg")));
+    }
+
+    #[test]
+    fn parse_fehbg_unescapes_quotes() {
+        let script = "#!/bin/sh\nfeh --no-fehbg --bg-scale '/home/me/it'\\''s.jpg' \n";
+        assert_eq!(parse_fehbg(script), Some(PathBuf::from("/home/me/it's.jpg")));
+    }
+
+    #[test]
+    fn parse_fehbg_without_feh_line() {
+        assert_eq!(parse_fehbg("#!/bin/sh\n"), None);
+    }
+
+    #[test]
+    fn desktop_from_session_names() {
+        // DESKTOP_SESSION and GDMSESSION name sessions rather than desktops
+        assert_eq!(desktop_from_name("plasma"), Some(DesktopEnvt::KDE));
+        assert_eq!(desktop_from_name("xfce"), Some(DesktopEnvt::XFCE));
+        assert_eq!(desktop_from_name("gnome-xorg"), Some(DesktopEnvt::GNOME));
+        assert_eq!(desktop_from_name(" Sway "), Some(DesktopEnvt::Sway));
+        assert_eq!(desktop_from_name("default"), None);
+    }
+
+    #[test]
+    fn desktop_from_colon_separated_names() {
+        assert_eq!(desktop_from_name("ubuntu:GNOME"), Some(DesktopEnvt::GNOME));
+        assert_eq!(desktop_from_name("pop:GNOME"), Some(DesktopEnvt::GNOME));
+        assert_eq!(desktop_from_name("X-Cinnamon"), Some(DesktopEnvt::Cinnamon));
+        // The first known entry wins
+        assert_eq!(desktop_from_name("unknown:KDE:GNOME"), Some(DesktopEnvt::KDE));
+    }
+
+    // Tests share the environment, so no other test may change it
+    #[test]
+    fn empty_desktop_variables_count_as_unset() {
+        for var in DESKTOP_VARS {
+            std::env::set_var(var, "");
+        }
+        for var in ["FLOWY_BACKEND", "SWAYSOCK", "HYPRLAND_INSTANCE_SIGNATURE", "DISPLAY", "WAYLAND_DISPLAY"] {
+            std::env::remove_var(var);
+        }
+        let err = DesktopEnvt::new().unwrap_err();
+        assert!(err.to_string().starts_with("None of XDG_CURRENT_DESKTOP"), "{}", err);
+    }
+}
@@ -1,34 +1,108 @@
-use super::Desktop;
+use super::{null, Capabilities, Desktop, WallpaperMode};
 use std::error::Error;
 use std::path::PathBuf;
 use std::process::Command;
 
+/// The macOS desktop
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub struct DesktopEnvt;
+pub enum DesktopEnvt {
+    MacOS,
+    /// Records wallpapers instead of setting them, for headless testing.
+    /// Selected with `FLOWY_BACKEND=null`.
+    Null,
+}
 
 impl Desktop for DesktopEnvt {
     fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(Self)
+        if null::selected() {
+            return Ok(DesktopEnvt::Null);
+        }
+        Ok(DesktopEnvt::MacOS)
     }
 
     fn set_wallpaper(&self, path: &str) -> Result<(), Box<dyn Error>> {
-        // Generate the Applescript string
-        let cmd = &format!(
-            r#"tell app "finder" to set desktop picture to POSIX file {}"#,
+        if *self == DesktopEnvt::Null {
+            return null::set_wallpaper(path);
+        }
+        // Finder's desktop picture only covers the main display,
+        // so every desktop of System Events is set instead
+        osascript(&format!(
+            r#"tell application "System Events" to tell every desktop to set picture to POSIX file {}"#,
             enquote::enquote('"', path),
-        );
-        // Run it using osascript
-        Command::new("osascript").args(&["-e", cmd]).output()?;
+        ))?;
 
         Ok(())
     }
 
     fn get_wallpaper(&self) -> Result<PathBuf, Box<dyn Error>> {
-        // Generate the Applescript string
-        let cmd = r#"tell app "finder" to get posix path of (get desktop picture as alias)"#;
-        // Run it using osascript
-        let output = Command::new("osascript").args(&["-e", cmd]).output()?;
+        // The wallpaper of the main display
+        self.get_wallpaper_for_monitor(0)
+    }
+
+    fn monitor_count(&self) -> Result<usize, Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return Ok(1);
+        }
+        let count = osascript(r#"tell application "System Events" to count desktops"#)?;
+        Ok(count.parse::<usize>()?.max(1))
+    }
+
+    fn monitor_names(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return Ok(vec!["0".into()]);
+        }
+        // Names come back as a comma separated list, e.g. "Built-in Retina Display, DELL U2720Q"
+        let names = osascript(r#"tell application "System Events" to get display name of every desktop"#)?;
+        Ok(names.split(", ").map(|n| n.to_string()).collect())
+    }
+
+    /// System Events can't change the scaling, so `mode` is ignored
+    fn set_wallpaper_for_monitor(
+        &self,
+        monitor: usize,
+        path: &str,
+        mode: WallpaperMode,
+    ) -> Result<(), Box<dyn Error>> {
+        let _ = mode;
+        if *self == DesktopEnvt::Null {
+            return null::set_wallpaper(path);
+        }
+        // Desktops are counted from 1 in AppleScript
+        osascript(&format!(
+            r#"tell application "System Events" to set picture of desktop {} to POSIX file {}"#,
+            monitor + 1,
+            enquote::enquote('"', path),
+        ))?;
+
+        Ok(())
+    }
+
+    fn get_wallpaper_for_monitor(&self, monitor: usize) -> Result<PathBuf, Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return null::get_wallpaper();
+        }
+        let path = osascript(&format!(
+            r#"tell application "System Events" to get picture of desktop {}"#,
+            monitor + 1
+        ))?;
+
+        Ok(path.into())
+    }
 
-        Ok(String::from_utf8(output.stdout)?.trim().into())
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            per_monitor: *self != DesktopEnvt::Null,
+            ..Capabilities::default()
+        }
     }
 }
+
+/// Runs an AppleScript and returns what it printed
+fn osascript(script: &str) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("osascript").args(["-e", script]).output()?;
+    if !output.status.success() {
+        return Err(format!("osascript failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().into())
+}
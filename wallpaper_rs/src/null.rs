@@ -0,0 +1,65 @@
+// THIS MODULE RECORDS WALLPAPERS INSTEAD OF SETTING THEM,
+// FOR RUNNING WITHOUT A DESKTOP
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// The last wallpaper recorded by this process
+static NULL_WALLPAPER: Mutex<Option<String>> = Mutex::new(None);
+
+/// Whether the null backend was asked for with `FLOWY_BACKEND=null`
+pub(crate) fn selected() -> bool {
+    std::env::var("FLOWY_BACKEND").as_deref() == Ok("null")
+}
+
+/// Appends the wallpaper to the file in FLOWY_BACKEND_LOG, or prints it
+pub(crate) fn set_wallpaper(path: &str) -> Result<(), Box<dyn Error>> {
+    match std::env::var_os("FLOWY_BACKEND_LOG") {
+        Some(log) => {
+            let mut log = OpenOptions::new().create(true).append(true).open(log)?;
+            writeln!(log, "{}", path)?;
+        }
+        None => println!("null backend: {}", path),
+    }
+    *NULL_WALLPAPER.lock().unwrap() = Some(path.to_string());
+
+    Ok(())
+}
+
+/// Returns the last wallpaper in FLOWY_BACKEND_LOG, so other processes
+/// see what the daemon set, or else the last one this process set
+pub(crate) fn get_wallpaper() -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(log) = std::env::var_os("FLOWY_BACKEND_LOG") {
+        if let Some(last) = fs::read_to_string(log).ok().as_deref().and_then(last_entry) {
+            return Ok(last.into());
+        }
+    }
+    NULL_WALLPAPER
+        .lock()
+        .unwrap()
+        .clone()
+        .map(PathBuf::from)
+        .ok_or_else(|| "No wallpaper has been set".into())
+}
+
+/// The last non-empty line of a backend log
+fn last_entry(log: &str) -> Option<&str> {
+    log.lines().rev().map(str::trim).find(|line| !line.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_entry_skips_trailing_blank_lines() {
+        assert_eq!(last_entry("/a.png\n/b.png\n\n"), Some("/b.png"));
+    }
+
+    #[test]
+    fn last_entry_of_empty_log() {
+        assert_eq!(last_entry(""), None);
+    }
+}
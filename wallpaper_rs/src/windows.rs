@@ -1,24 +1,48 @@
-use super::Desktop;
+use super::{null, Capabilities, Desktop, WallpaperMode};
 use std::error::Error;
 use std::ffi::OsStr;
 use std::io;
-use std::os::raw::c_void;
 use std::os::windows::ffi::OsStrExt;
 use std::path::PathBuf;
+use std::ptr::null_mut;
+use winapi::ctypes::c_void;
+use winapi::shared::winerror::{FAILED, RPC_E_CHANGED_MODE};
+use winapi::shared::ntdef::LPWSTR;
+use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CLSCTX_ALL};
+use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+use winapi::um::shobjidl_core::{
+    CLSID_DesktopWallpaper, IDesktopWallpaper, DESKTOP_WALLPAPER_POSITION, DWPOS_CENTER,
+    DWPOS_FILL, DWPOS_FIT, DWPOS_STRETCH, DWPOS_TILE,
+};
+use winapi::um::winnt::REG_SZ;
+use winapi::um::winreg::{RegSetKeyValueW, HKEY_CURRENT_USER};
+use winapi::Interface;
 use winapi::um::winuser::{
-    SystemParametersInfoW, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE, SPI_GETDESKWALLPAPER,
-    SPI_SETDESKWALLPAPER,
+    GetSystemMetrics, SystemParametersInfoW, SM_CMONITORS, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE,
+    SPI_GETDESKWALLPAPER, SPI_SETDESKWALLPAPER,
 };
 
+/// The Windows desktop
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub struct DesktopEnvt;
+pub enum DesktopEnvt {
+    Windows,
+    /// Records wallpapers instead of setting them, for headless testing.
+    /// Selected with `FLOWY_BACKEND=null`.
+    Null,
+}
 
 impl Desktop for DesktopEnvt {
     fn new() -> Result<Self, Box<dyn Error>> {
-        Ok(Self)
+        if null::selected() {
+            return Ok(DesktopEnvt::Null);
+        }
+        Ok(DesktopEnvt::Windows)
     }
 
     fn set_wallpaper(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return null::set_wallpaper(path);
+        }
         let mut path: Vec<u16> = OsStr::new(path).encode_wide().collect();
         // append null byte
         path.push(0);
@@ -39,7 +63,28 @@ impl Desktop for DesktopEnvt {
         }
     }
 
+    fn set_wallpaper_with_mode(&self, path: &str, mode: WallpaperMode) -> Result<(), Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return null::set_wallpaper(path);
+        }
+        // Tiling is a separate setting from the style
+        let (style, tile) = match mode {
+            WallpaperMode::Fill => ("10", "0"),
+            WallpaperMode::Fit => ("6", "0"),
+            WallpaperMode::Center => ("0", "0"),
+            WallpaperMode::Stretch => ("2", "0"),
+            WallpaperMode::Tile => ("0", "1"),
+        };
+        set_desktop_value("WallpaperStyle", style)?;
+        set_desktop_value("TileWallpaper", tile)?;
+        // The style is only read when the wallpaper is set
+        self.set_wallpaper(path)
+    }
+
     fn get_wallpaper(&self) -> Result<PathBuf, Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return null::get_wallpaper();
+        }
         let buffer: [u16; 260] = unsafe { std::mem::zeroed() };
         let successful = unsafe {
             SystemParametersInfoW(
@@ -62,4 +107,168 @@ impl Desktop for DesktopEnvt {
             Err(io::Error::last_os_error().into())
         }
     }
+
+    fn monitor_count(&self) -> Result<usize, Box<dyn Error>> {
+        if *self == DesktopEnvt::Null {
+            return Ok(1);
+        }
+        match DesktopWallpaper::new() {
+            Ok(com) => com.monitor_count(),
+            Err(_) => Ok(unsafe { GetSystemMetrics(SM_CMONITORS) }.max(1) as usize),
+        }
+    }
+
+    fn set_wallpaper_for_monitor(
+        &self,
+        monitor: usize,
+        path: &str,
+        mode: WallpaperMode,
+    ) -> Result<(), Box<dyn Error>> {
+        // Without COM, e.g. on Windows 7, every monitor gets the wallpaper
+        let com = match DesktopWallpaper::new() {
+            Ok(com) if *self != DesktopEnvt::Null => com,
+            _ => return self.set_wallpaper_with_mode(path, mode),
+        };
+        // The position applies to all monitors
+        let position: DESKTOP_WALLPAPER_POSITION = match mode {
+            WallpaperMode::Fill => DWPOS_FILL,
+            WallpaperMode::Fit => DWPOS_FIT,
+            WallpaperMode::Center => DWPOS_CENTER,
+            WallpaperMode::Stretch => DWPOS_STRETCH,
+            WallpaperMode::Tile => DWPOS_TILE,
+        };
+        let id = com.monitor_id(monitor)?;
+        let path = wide(path);
+        unsafe {
+            check((*com.0).SetPosition(position))?;
+            check((*com.0).SetWallpaper(id.as_ptr(), path.as_ptr()))
+        }
+    }
+
+    fn get_wallpaper_for_monitor(&self, monitor: usize) -> Result<PathBuf, Box<dyn Error>> {
+        let com = match DesktopWallpaper::new() {
+            Ok(com) if *self != DesktopEnvt::Null => com,
+            _ => return self.get_wallpaper(),
+        };
+        let id = com.monitor_id(monitor)?;
+        let mut wallpaper: LPWSTR = null_mut();
+        unsafe {
+            check((*com.0).GetWallpaper(id.as_ptr(), &mut wallpaper))?;
+            Ok(take_string(wallpaper)?.into())
+        }
+    }
+
+    // Windows needs extensions from the Store for these
+    fn unsupported_formats(&self) -> &'static [&'static str] {
+        match self {
+            DesktopEnvt::Windows => &["avif", "heic", "heif"],
+            DesktopEnvt::Null => &[],
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            per_monitor: *self != DesktopEnvt::Null && DesktopWallpaper::new().is_ok(),
+            ..Capabilities::default()
+        }
+    }
+}
+
+/// Returns `s` as a null terminated UTF-16 string
+fn wide(s: &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(std::iter::once(0)).collect()
+}
+
+/// Writes a string value to the desktop settings in the registry
+fn set_desktop_value(name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let (key, name, value) = (wide("Control Panel\\Desktop"), wide(name), wide(value));
+    let status = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            name.as_ptr(),
+            REG_SZ,
+            value.as_ptr() as *const c_void,
+            (value.len() * 2) as u32,
+        )
+    };
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::from_raw_os_error(status).into())
+    }
+}
+
+/// The shell's `IDesktopWallpaper` object, which can set the wallpaper of
+/// each monitor. It is available from Windows 8.
+struct DesktopWallpaper(*mut IDesktopWallpaper);
+
+impl DesktopWallpaper {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        let mut com: *mut c_void = null_mut();
+        unsafe {
+            // COM stays initialized for the rest of the thread. A thread which
+            // already initialized it differently can still create the object.
+            let result = CoInitializeEx(null_mut(), COINIT_APARTMENTTHREADED);
+            if result != RPC_E_CHANGED_MODE {
+                check(result)?;
+            }
+            check(CoCreateInstance(
+                &CLSID_DesktopWallpaper,
+                null_mut(),
+                CLSCTX_ALL,
+                &IDesktopWallpaper::uuidof(),
+                &mut com,
+            ))?;
+        }
+        Ok(DesktopWallpaper(com as *mut IDesktopWallpaper))
+    }
+
+    fn monitor_count(&self) -> Result<usize, Box<dyn Error>> {
+        let mut count = 0;
+        unsafe { check((*self.0).GetMonitorDevicePathCount(&mut count))? };
+        Ok(count as usize)
+    }
+
+    /// Returns the null terminated device path of a monitor, counting from 0
+    fn monitor_id(&self, monitor: usize) -> Result<Vec<u16>, Box<dyn Error>> {
+        let count = self.monitor_count()?;
+        if monitor >= count {
+            return Err(format!("No monitor {}, found {} monitors", monitor, count).into());
+        }
+        let mut id: LPWSTR = null_mut();
+        unsafe {
+            check((*self.0).GetMonitorDevicePathAt(monitor as u32, &mut id))?;
+            Ok(wide(&take_string(id)?))
+        }
+    }
+}
+
+impl Drop for DesktopWallpaper {
+    fn drop(&mut self) {
+        unsafe { (*self.0).Release() };
+    }
+}
+
+/// Turns a failed COM call into an error
+fn check(result: i32) -> Result<(), Box<dyn Error>> {
+    if FAILED(result) {
+        Err(io::Error::from_raw_os_error(result).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Copies a string allocated by COM and frees it
+unsafe fn take_string(s: LPWSTR) -> Result<String, Box<dyn Error>> {
+    if s.is_null() {
+        return Err("No string was returned".into());
+    }
+    let mut len = 0;
+    while *s.add(len) != 0 {
+        len += 1;
+    }
+    let string = String::from_utf16(std::slice::from_raw_parts(s, len));
+    CoTaskMemFree(s as *mut c_void);
+    Ok(string?)
 }